@@ -0,0 +1,642 @@
+use crate::fmatter;
+use crate::utils::{
+    is_not_hidden, is_valid_tag, load_config, load_dir_meta, normalize_alias, output_rel_path,
+    slugify, Config, DirMeta, Post, PostTyp, SiteIndexes, DIR_META_FILE,
+};
+use serde::de::{DeserializeOwned, IgnoredAny, Visitor};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Report {
+    issues: Vec<Issue>,
+    // output path -> source file
+    outputs: BTreeMap<String, String>,
+    // (alias, source file), claimed after all regular outputs
+    aliases: Vec<(String, String)>,
+    // indexes generated from the listed posts
+    site: SiteIndexes,
+    // (original, source file) of translated posts
+    translations: Vec<(String, String)>,
+}
+
+impl Report {
+    fn push(&mut self, severity: Severity, file: &Path, line: Option<usize>, message: String) {
+        self.issues.push(Issue {
+            severity,
+            file: Some(file.display().to_string()),
+            line,
+            message,
+        });
+    }
+
+    fn claim_output(&mut self, out: String, src: &Path) {
+        let src = src.display().to_string();
+        if let Some(prev) = self.outputs.get(&out) {
+            let message = format!("output path {} is also generated by {}", out, prev);
+            self.issues.push(Issue {
+                severity: Severity::Error,
+                file: Some(src),
+                line: None,
+                message,
+            });
+        } else {
+            self.outputs.insert(out, src);
+        }
+    }
+}
+
+/// captures the field names of a struct deriving `Deserialize`
+struct FieldsProbe<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> serde::Deserializer<'de> for FieldsProbe<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(serde::de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(serde::de::Error::custom("fields captured"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// returns the keys which aren't fields of `T`; this is a separate pass,
+/// so that the deserialization of `T` itself stays as strict as in `build`
+fn unknown_keys<T: DeserializeOwned>(keys: BTreeMap<String, IgnoredAny>) -> Vec<String> {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsProbe(&mut fields));
+    keys.into_keys()
+        .filter(|i| !fields.contains(&i.as_str()))
+        .collect()
+}
+
+fn check_post(
+    report: &mut Report,
+    config: Option<&Config>,
+    indir: &Path,
+    path: &Path,
+    // series slug -> part number -> first post with that part
+    series: &mut BTreeMap<String, BTreeMap<u32, String>>,
+) {
+    use Severity::*;
+
+    let fh_data = match std::fs::read(path) {
+        Ok(x) => x,
+        Err(e) => return report.push(Error, path, None, format!("unable to read file: {}", e)),
+    };
+    let fh_data = match std::str::from_utf8(&fh_data) {
+        Ok(x) => fmatter::normalize_newlines(x),
+        Err(e) => return report.push(Error, path, None, format!("invalid UTF-8: {}", e)),
+    };
+    let (post, content, keys) = match fmatter::split(&fh_data)
+        .and_then(|(fm, content)| Ok((fm.parse::<Post>()?, content, fm.parse()?)))
+    {
+        Ok(x) => x,
+        Err(e) => {
+            return report.push(
+                Error,
+                path,
+                e.line,
+                format!("invalid front matter: {}", e.msg),
+            )
+        }
+    };
+    for i in unknown_keys::<Post>(keys) {
+        report.push(
+            Warning,
+            path,
+            None,
+            format!("unknown front matter key: {}", i),
+        );
+    }
+    if post.title.is_empty() && !post.is_untitled_typ() {
+        report.push(Error, path, None, "missing title".to_string());
+    }
+    if post.link_target(content) == Some("") {
+        report.push(Error, path, None, "link post without target".to_string());
+    }
+    if post.typ == PostTyp::Image && post.src.is_empty() {
+        report.push(Error, path, None, "image post without src".to_string());
+    }
+
+    let srcrel: &camino::Utf8Path = match path.strip_prefix(indir).unwrap().try_into() {
+        Ok(x) => x,
+        Err(_) => return report.push(Error, path, None, "invalid file name".to_string()),
+    };
+    // only listed posts appear in indexes
+    let listed = !post.unlisted && post.typ != PostTyp::Page;
+    if let Some(lang) = &post.lang {
+        if !is_valid_tag(lang) {
+            report.push(Error, path, None, format!("invalid language: {}", lang));
+        } else if listed && !matches!(config, Some(c) if c.lang == *lang) {
+            report.site.langs.insert(lang.clone());
+        }
+    }
+    if let Some(x) = &post.translation_of {
+        report.translations.push((
+            x.trim_start_matches('/').to_string(),
+            path.display().to_string(),
+        ));
+    }
+    let cdate = match (post.typ, yz_diary_date::parse_from_utf8path(srcrel)) {
+        (PostTyp::Page, _) => Some(None),
+        (_, Some(cdate)) => Some(Some(cdate)),
+        (_, None) => {
+            report.push(
+                Error,
+                path,
+                None,
+                "file name without parsable diary date".to_string(),
+            );
+            None
+        }
+    };
+    let fpap = match (config, cdate) {
+        // without a valid config, the output path can't be determined
+        (Some(config), Some(cdate)) => match output_rel_path(config, srcrel, &post, cdate) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                report.push(Error, path, None, e);
+                None
+            }
+        },
+        _ => None,
+    };
+    if let PostTyp::Page = post.typ {
+        // pages don't get tag pages
+        if !post.tags.is_empty() {
+            report.push(Warning, path, None, "tags of pages are ignored".to_string());
+        }
+    } else {
+        for i in &post.tags {
+            let slug = slugify(i);
+            if !is_valid_tag(&slug) {
+                report.push(Error, path, None, format!("invalid tag: {}", i));
+            } else if listed {
+                report.site.tags.insert(slug);
+            }
+        }
+    }
+    if let Some(x) = &post.series {
+        let slug = slugify(x.name());
+        if !is_valid_tag(&slug) {
+            report.push(
+                Error,
+                path,
+                None,
+                format!("invalid series name: {}", x.name()),
+            );
+        } else {
+            if listed {
+                report.site.series.insert(slug.clone());
+            }
+            let parts = series.entry(slug).or_default();
+            if let Some(part) = x.part() {
+                match parts.get(&part) {
+                    Some(prev) => report.push(
+                        Warning,
+                        path,
+                        None,
+                        format!(
+                            "duplicate part {} of series {}, also used by {}",
+                            part,
+                            x.name(),
+                            prev
+                        ),
+                    ),
+                    None => {
+                        parts.insert(part, path.display().to_string());
+                    }
+                }
+            }
+        }
+    }
+    if let (Some(config), true) = (config, listed) {
+        for i in post.author_ids() {
+            if config.authors.contains_key(&i) && is_valid_tag(&i) {
+                report.site.authors.insert(i);
+            }
+        }
+    }
+    if let Some(fpap) = fpap {
+        if listed {
+            let dir = fpap.parent().unwrap_or(camino::Utf8Path::new(""));
+            report.site.dirs.insert(dir.to_path_buf());
+        }
+        if post.has_page(content) {
+            report.claim_output(fpap.into_string(), path);
+        }
+    }
+    for i in &post.aliases {
+        report.aliases.push((i.clone(), path.display().to_string()));
+    }
+}
+
+/// validates the config and all posts, returns all found issues and the number of checked files
+fn validate(config_path: &Path, indir: &Path) -> (Vec<Issue>, usize) {
+    let mut report = Report::default();
+
+    let config = match load_config(config_path) {
+        Ok((config, _)) => Some(config),
+        Err(e) => {
+            report.push(
+                Severity::Error,
+                config_path,
+                None,
+                format!("unable to load config: {}", e),
+            );
+            None
+        }
+    };
+
+    let mut series = BTreeMap::new();
+    // source paths of all posts, relative to the input directory
+    let mut srcrels = BTreeSet::new();
+    let mut nfiles = 0;
+    for dirent in walkdir::WalkDir::new(indir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(is_not_hidden)
+    {
+        let dirent = match dirent {
+            Ok(x) => x,
+            Err(e) => {
+                report.issues.push(Issue {
+                    severity: Severity::Error,
+                    file: e.path().map(|p| p.display().to_string()),
+                    line: None,
+                    message: format!("walkdir error: {}", e),
+                });
+                continue;
+            }
+        };
+        if dirent.file_type().is_dir() {
+            continue;
+        }
+        if dirent.file_name() == DIR_META_FILE {
+            let keys = std::fs::read(dirent.path())
+                .ok()
+                .and_then(|x| serde_yaml::from_slice(&x).ok())
+                .unwrap_or_default();
+            match load_dir_meta(dirent.path()) {
+                Ok(_) => {
+                    for i in unknown_keys::<DirMeta>(keys) {
+                        report.push(
+                            Severity::Warning,
+                            dirent.path(),
+                            None,
+                            format!("unknown directory metadata key: {}", i),
+                        );
+                    }
+                }
+                Err(e) => report.push(
+                    Severity::Error,
+                    dirent.path(),
+                    None,
+                    format!("invalid directory metadata: {}", e),
+                ),
+            }
+            nfiles += 1;
+        } else {
+            if let Ok(x) = dirent.path().strip_prefix(indir) {
+                srcrels.insert(x.to_string_lossy().into_owned());
+            }
+            check_post(
+                &mut report,
+                config.as_ref(),
+                indir,
+                dirent.path(),
+                &mut series,
+            );
+            nfiles += 1;
+        }
+    }
+
+    // outputs which are generated in addition to the posts, like the build does
+    if let Some(config) = &config {
+        for (out, src) in report.site.outputs(config) {
+            report.claim_output(out.into_string(), Path::new(&src));
+        }
+    }
+    for (orig, src) in std::mem::take(&mut report.translations) {
+        if !srcrels.contains(&orig) {
+            report.push(
+                Severity::Error,
+                Path::new(&src),
+                None,
+                format!("translation of unknown post: {}", orig),
+            );
+        }
+    }
+    if let Some(config) = &config {
+        for i in config.redirects.keys() {
+            report
+                .aliases
+                .push((i.clone(), config_path.display().to_string()));
+        }
+    }
+    for (alias, src) in std::mem::take(&mut report.aliases) {
+        match normalize_alias(&alias) {
+            Ok(x) => report.claim_output(x.into_string(), Path::new(&src)),
+            Err(e) => report.push(Severity::Error, Path::new(&src), None, e),
+        }
+    }
+    if let Some(config) = &config {
+        for i in config.authors.keys() {
+            if !is_valid_tag(i) {
+                report.push(
+                    Severity::Error,
+                    config_path,
+                    None,
+                    format!("invalid author id: {}", i),
+                );
+            }
+        }
+    }
+
+    (report.issues, nfiles)
+}
+
+fn is_ok(issues: &[Issue]) -> bool {
+    !issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+fn json_report(issues: &[Issue]) -> serde_json::Value {
+    serde_json::json!({ "ok": is_ok(issues), "issues": issues })
+}
+
+/// validates the config and all posts, returns `true` if no errors were found
+pub fn run(config_path: &Path, indir: &Path, json: bool) -> bool {
+    let (issues, nfiles) = validate(config_path, indir);
+    if json {
+        println!("{}", json_report(&issues));
+    } else {
+        for i in &issues {
+            let sev = match i.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            match (&i.file, i.line) {
+                (Some(f), Some(l)) => println!("{}: {}:{}: {}", sev, f, l, i.message),
+                (Some(f), None) => println!("{}: {}: {}", sev, f, i.message),
+                (None, _) => println!("{}: {}", sev, i.message),
+            }
+        }
+        println!(
+            "{} file(s) checked, {} issue(s) found",
+            nfiles,
+            issues.len()
+        );
+    }
+    is_ok(&issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const CONFIG: &str = "blog_name: x\nid: 'https://example.com/blog'\nauthor: x\nstylesheet: x\n";
+
+    /// writes the config and the given posts into a fresh directory, returns its path
+    fn site(name: &str, posts: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("zsstwebr-check-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("posts")).unwrap();
+        std::fs::write(dir.join("config.yaml"), CONFIG).unwrap();
+        for (path, data) in posts {
+            let path = dir.join("posts").join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    fn issues(name: &str, posts: &[(&str, &str)]) -> Vec<Issue> {
+        let dir = site(name, posts);
+        let (ret, _) = validate(&dir.join("config.yaml"), &dir.join("posts"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        ret
+    }
+
+    fn messages(issues: &[Issue]) -> Vec<(Severity, &str)> {
+        issues
+            .iter()
+            .map(|i| (i.severity, i.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        let dir = site(
+            "clean",
+            &[
+                ("2021/0101a", "title: a\ntyp: text\ntags: [x]\n---\nhi\n"),
+                ("imprint", "title: i\ntyp: page\n---\nhi\n"),
+            ],
+        );
+        let (x, nfiles) = validate(&dir.join("config.yaml"), &dir.join("posts"));
+        assert_eq!(messages(&x), []);
+        assert_eq!(nfiles, 2);
+        assert!(run(&dir.join("config.yaml"), &dir.join("posts"), false));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_outputs() {
+        let x = issues(
+            "dups",
+            &[
+                ("2021/0101a", "title: a\ntyp: text\nslug: 0101b\n---\nhi\n"),
+                ("2021/0101b", "title: b\ntyp: text\n---\nhi\n"),
+                (
+                    "2021/0102c",
+                    "title: c\ntyp: text\naliases: [2021/0101a.html, x.html]\n---\nhi\n",
+                ),
+                (
+                    "2021/0103d",
+                    "title: d\ntyp: text\naliases: [x.html]\n---\nhi\n",
+                ),
+            ],
+        );
+        let x = messages(&x);
+        assert_eq!(x.len(), 2);
+        assert!(x[0]
+            .1
+            .starts_with("output path 2021/0101b.html is also generated by "));
+        assert!(x[1]
+            .1
+            .starts_with("output path x.html is also generated by "));
+        assert!(x.iter().all(|i| i.0 == Severity::Error));
+    }
+
+    #[test]
+    fn generated_outputs() {
+        let posts = [
+            // no search page, no directory index for pages, no tag index for unlisted posts
+            ("search", "title: s\ntyp: page\n---\nhi\n"),
+            ("pages/index", "title: i\ntyp: page\n---\nhi\n"),
+            ("x", "title: x\ntyp: page\n---\nhi\n"),
+            (
+                "2021/0101a",
+                "title: a\ntyp: text\nunlisted: true\ntags: [x]\n---\nhi\n",
+            ),
+            ("2021/0102b", "title: b\ntyp: text\ntags: [y]\n---\nhi\n"),
+            ("y", "title: y\ntyp: page\n---\nhi\n"),
+        ];
+        let dir = site("generated", &posts);
+        let config = format!("{}search: {{enabled: true}}\n", CONFIG);
+        std::fs::write(dir.join("config.yaml"), config).unwrap();
+        let (x, _) = validate(&dir.join("config.yaml"), &dir.join("posts"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        // only the tag index of the listed post collides
+        assert_eq!(x.len(), 1);
+        assert_eq!(x[0].file.as_deref(), Some("(tag y)"));
+        assert!(x[0]
+            .message
+            .starts_with("output path y.html is also generated by "));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let x = issues(
+            "keys",
+            &[
+                ("2021/0101a", "title: a\ntyp: text\nbogus: 1\n---\nhi\n"),
+                ("fld/_dir.yaml", "title: F\nfoo: 2\n"),
+                ("fld/2021/0101b", "title: b\ntyp: text\n---\nhi\n"),
+            ],
+        );
+        assert_eq!(
+            messages(&x),
+            [
+                (Severity::Warning, "unknown front matter key: bogus"),
+                (Severity::Warning, "unknown directory metadata key: foo"),
+            ]
+        );
+        assert!(is_ok(&x));
+    }
+
+    #[test]
+    fn invalid_posts() {
+        let x = issues(
+            "invalid",
+            &[
+                ("2021/0101a", "typ: text\n---\nhi\n"),
+                (
+                    "2021/0102b",
+                    "title: b\ntyp: text\ntags: ['C/C++', Node.js, '?!']\n---\nhi\n",
+                ),
+                (
+                    "2021/0103c",
+                    "title: c\ntyp: text\ntranslation_of: 2021/0109x\n---\nhi\n",
+                ),
+                (
+                    "2021/0104d",
+                    "title: d\ntyp: text\nupdated: 2021-13-01\n---\nhi\n",
+                ),
+                ("2021/hello", "title: e\ntyp: text\n---\nhi\n"),
+            ],
+        );
+        let x = messages(&x);
+        assert_eq!(x.len(), 5);
+        assert_eq!(
+            x[..2],
+            [
+                (Severity::Error, "missing title"),
+                (Severity::Error, "invalid tag: ?!"),
+            ]
+        );
+        assert!(x[2].1.starts_with("invalid front matter: "));
+        assert_eq!(
+            x[3..],
+            [
+                (Severity::Error, "file name without parsable diary date"),
+                (Severity::Error, "translation of unknown post: 2021/0109x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn series_parts() {
+        let x = issues(
+            "series",
+            &[
+                (
+                    "2021/0101a",
+                    "title: a\ntyp: text\nseries: {name: S, part: 1}\n---\nhi\n",
+                ),
+                (
+                    "2021/0102b",
+                    "title: b\ntyp: text\nseries: {name: S, part: 1}\n---\nhi\n",
+                ),
+                (
+                    "2021/0103c",
+                    "title: c\ntyp: text\nseries: {name: S, part: 2}\n---\nhi\n",
+                ),
+                ("2021/0104d", "title: d\ntyp: text\nseries: S\n---\nhi\n"),
+            ],
+        );
+        let x = messages(&x);
+        assert_eq!(x.len(), 1);
+        assert_eq!(x[0].0, Severity::Warning);
+        assert!(x[0]
+            .1
+            .starts_with("duplicate part 1 of series S, also used by "));
+        assert!(x[0].1.ends_with("0101a"));
+    }
+
+    #[test]
+    fn json_shape() {
+        let dir = site(
+            "json",
+            &[("2021/0101a", "title: a\ntyp: text\nx: [\n---\nhi\n")],
+        );
+        let (x, _) = validate(&dir.join("config.yaml"), &dir.join("posts"));
+        // errors result in a non-zero exit code
+        assert!(!run(&dir.join("config.yaml"), &dir.join("posts"), true));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let v = json_report(&x);
+        assert_eq!(v["ok"], false);
+        let i = &v["issues"][0];
+        assert_eq!(i["severity"], "error");
+        assert!(i["file"].as_str().unwrap().ends_with("0101a"));
+        assert!(i["line"].is_u64());
+        assert!(i["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid front matter"));
+    }
+}
@@ -0,0 +1,233 @@
+//! front matter parsing
+//!
+//! Supported formats:
+//! - YAML, terminated by a line containing only `---`, optionally also preceded by such a line
+//! - TOML, enclosed in lines containing only `+++`
+//! - a JSON object at the start of the file
+
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+pub struct FrontMatter<'a> {
+    pub format: Format,
+    pub text: &'a str,
+    // number of lines before `text` starts
+    line_offset: usize,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    // 1-based line number in the input file
+    pub line: Option<usize>,
+    pub msg: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.msg),
+            None => f.write_str(&self.msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// converts CRLF line endings to LF
+pub fn normalize_newlines(data: &str) -> Cow<'_, str> {
+    if data.contains('\r') {
+        data.replace("\r\n", "\n").into()
+    } else {
+        data.into()
+    }
+}
+
+/// searches for a line consisting only of `delim`,
+/// returns the start of the line and the start of the next line
+fn find_delim_line(data: &str, delim: &str) -> Option<(usize, usize)> {
+    let mut pos = 0;
+    for line in data.split_inclusive('\n') {
+        if line.trim_end_matches('\n') == delim {
+            return Some((pos, pos + line.len()));
+        }
+        pos += line.len();
+    }
+    None
+}
+
+/// splits `data` (with normalized newlines) into front matter and content
+pub fn split(data: &str) -> Result<(FrontMatter<'_>, &str), Error> {
+    let data = data.strip_prefix('\u{feff}').unwrap_or(data);
+    let fenced = |format, delim: &str| {
+        let rest = &data[delim.len() + 1..];
+        match find_delim_line(rest, delim) {
+            Some((hend, cstart)) => Ok((
+                FrontMatter {
+                    format,
+                    text: &rest[..hend],
+                    line_offset: 1,
+                },
+                &rest[cstart..],
+            )),
+            None => Err(Error {
+                line: Some(1),
+                msg: format!("front matter starting here isn't terminated by `{}`", delim),
+            }),
+        }
+    };
+
+    if data.starts_with("---\n") {
+        fenced(Format::Yaml, "---")
+    } else if data.starts_with("+++\n") {
+        fenced(Format::Toml, "+++")
+    } else if data.starts_with('{') {
+        let mut it = serde_json::Deserializer::from_str(data).into_iter::<serde_json::Value>();
+        match it.next() {
+            Some(Ok(_)) => {
+                let hend = it.byte_offset();
+                let rest = &data[hend..];
+                // skip the remainder of the line containing the closing brace
+                let cstart = rest.find('\n').map(|i| i + 1).unwrap_or(rest.len());
+                Ok((
+                    FrontMatter {
+                        format: Format::Json,
+                        text: &data[..hend],
+                        line_offset: 0,
+                    },
+                    &rest[cstart..],
+                ))
+            }
+            Some(Err(e)) => Err(Error {
+                line: Some(e.line()),
+                msg: e.to_string(),
+            }),
+            None => unreachable!(),
+        }
+    } else {
+        match find_delim_line(data, "---") {
+            Some((hend, cstart)) => Ok((
+                FrontMatter {
+                    format: Format::Yaml,
+                    text: &data[..hend],
+                    line_offset: 0,
+                },
+                &data[cstart..],
+            )),
+            None => Err(Error {
+                line: None,
+                msg: "unable to find end of front matter (a line containing only `---`)"
+                    .to_string(),
+            }),
+        }
+    }
+}
+
+impl FrontMatter<'_> {
+    fn line_at(&self, byte_pos: usize) -> usize {
+        self.line_offset
+            + 1
+            + self.text[..byte_pos.min(self.text.len())]
+                .matches('\n')
+                .count()
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        match self.format {
+            Format::Yaml => serde_yaml::from_str(self.text).map_err(|e| Error {
+                line: e.location().map(|l| self.line_offset + l.line()),
+                msg: e.to_string(),
+            }),
+            Format::Toml => toml::from_str(self.text).map_err(|e| Error {
+                line: e.span().map(|s| self.line_at(s.start)),
+                msg: e.message().to_string(),
+            }),
+            Format::Json => serde_json::from_str(self.text).map_err(|e| Error {
+                line: Some(self.line_offset + e.line()),
+                msg: e.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    type Map = BTreeMap<String, String>;
+
+    fn parse(data: &str) -> Result<(Map, String), Error> {
+        let data = normalize_newlines(data);
+        let (fm, content) = split(&data)?;
+        Ok((fm.parse()?, content.to_string()))
+    }
+
+    fn map(title: &str) -> Map {
+        let mut ret = Map::new();
+        ret.insert("title".to_string(), title.to_string());
+        ret
+    }
+
+    #[test]
+    fn yaml() {
+        assert_eq!(
+            parse("title: a\n---\nbody\n").unwrap(),
+            (map("a"), "body\n".to_string())
+        );
+        assert_eq!(
+            parse("---\ntitle: a\n---\nbody\n").unwrap(),
+            (map("a"), "body\n".to_string())
+        );
+        assert_eq!(parse("title: a\n---").unwrap(), (map("a"), String::new()));
+    }
+
+    #[test]
+    fn crlf() {
+        assert_eq!(
+            parse("---\r\ntitle: a\r\n---\r\nbody\r\n\r\nmore").unwrap(),
+            (map("a"), "body\n\nmore".to_string())
+        );
+    }
+
+    #[test]
+    fn toml() {
+        assert_eq!(
+            parse("+++\ntitle = \"a\"\n+++\nbody").unwrap(),
+            (map("a"), "body".to_string())
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            parse("{\n  \"title\": \"a\"\n}\nbody").unwrap(),
+            (map("a"), "body".to_string())
+        );
+    }
+
+    #[test]
+    fn error_lines() {
+        assert_eq!(
+            parse("---\ntitle: a\nx: [\n---\n").unwrap_err().line,
+            Some(3)
+        );
+        assert_eq!(
+            parse("+++\ntitle = \"a\"\nx = \n+++\n").unwrap_err().line,
+            Some(3)
+        );
+        assert_eq!(
+            parse("{\n\"title\": \"a\",\n}\n").unwrap_err().line,
+            Some(3)
+        );
+        assert_eq!(parse("+++\ntitle = \"a\"\n").unwrap_err().line, Some(1));
+        assert_eq!(parse("title: a\nbody").unwrap_err().line, None);
+    }
+}
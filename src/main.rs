@@ -1,16 +1,18 @@
+mod check;
+mod fmatter;
 mod ofmt;
+mod outfile;
+mod scaffold;
+mod toc;
+mod typo;
 mod utils;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::{convert::TryInto, fs::File, path::Path};
 
 fn main() {
-    use crate::ofmt::{write_article_page, write_feed, write_index};
-    use crate::utils::*;
     use clap::{Arg, ArgAction};
 
-    let null_path = Path::new("");
-
     let matches = clap::Command::new("zsstwebr")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Alain Zscheile <zseri.devel@ytrizja.de>")
@@ -46,79 +48,301 @@ fn main() {
                 .help("force overwriting of destination files even if the source files weren't modified")
                 .action(ArgAction::SetTrue),
         )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            clap::Command::new("check")
+                .about("validates the config and all posts without writing any output")
+                .arg(
+                    Arg::new("INPUT_DIR")
+                        .help("sets the input directory")
+                        .action(ArgAction::Set)
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("config")
+                        .long("config")
+                        .help("sets the config file path")
+                        .action(ArgAction::Set)
+                        .num_args(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("print the results as JSON")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("new")
+                .about("creates a new post dated today")
+                .arg(
+                    Arg::new("INPUT_DIR")
+                        .help("sets the input directory")
+                        .action(ArgAction::Set)
+                        .default_value(".")
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("dir")
+                        .long("dir")
+                        .help("sets the subdirectory (relative to the input directory)")
+                        .action(ArgAction::Set)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("title")
+                        .long("title")
+                        .help("sets the post title")
+                        .action(ArgAction::Set)
+                        .num_args(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("tags")
+                        .long("tags")
+                        .help("sets the (comma-separated) tags")
+                        .action(ArgAction::Set)
+                        .num_args(1)
+                        .value_delimiter(','),
+                )
+                .arg(
+                    Arg::new("link")
+                        .long("link")
+                        .help("creates a link post pointing to the given URL")
+                        .action(ArgAction::Set)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("edit")
+                        .short('e')
+                        .long("edit")
+                        .help("opens the new post in $VISUAL or $EDITOR")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
         .get_matches();
 
-    let mangler = Mangler::new(&[
-        "address",
-        "article",
-        "aside",
-        "blockquote",
-        "code",
-        "div",
-        "dl",
-        "fieldset",
-        "footer",
-        "form",
-        "h1",
-        "h2",
-        "h3",
-        "h4",
-        "h5",
-        "h6",
-        "header",
-        "hr",
-        "menu",
-        "nav",
-        "ol",
-        "p",
-        "pre",
-        "section",
-        "table",
-        "tt",
-        "ul",
-    ]);
+    match matches.subcommand() {
+        Some(("new", m)) => {
+            let fpath = scaffold::run(
+                Path::new(m.get_one::<String>("INPUT_DIR").unwrap()),
+                m.get_one::<String>("dir").map(String::as_str),
+                m.get_one::<String>("title").unwrap(),
+                m.get_many::<String>("tags")
+                    .map(|i| {
+                        i.map(|t| t.trim().to_string())
+                            .filter(|t| !t.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                m.get_one::<String>("link").map(String::as_str),
+            )
+            .expect("unable to create post");
+            println!("{}", fpath.display());
+            if m.get_flag("edit") {
+                scaffold::edit(&fpath).expect("unable to start editor");
+            }
+        }
+        Some(("check", m)) => {
+            let ok = check::run(
+                Path::new(m.get_one::<String>("config").unwrap()),
+                Path::new(m.get_one::<String>("INPUT_DIR").unwrap()),
+                m.get_flag("json"),
+            );
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        _ => build(&matches),
+    }
+}
+
+/// parses a post, untitled posts get a title derived from their content
+fn parse_post<'a>(path: &Path, data: &'a str) -> (utils::Post, &'a str) {
+    let (mut rd, content): (utils::Post, &str) = fmatter::split(data)
+        .and_then(|(fm, content)| Ok((fm.parse()?, content)))
+        .unwrap_or_else(|e| panic!("unable to parse front matter of {}: {}", path.display(), e));
+    if rd.title.is_empty() {
+        if !rd.is_untitled_typ() {
+            panic!("missing title in {}", path.display());
+        }
+        rd.title = rd.fallback_title(content);
+    }
+    (rd, content)
+}
+
+/// registers a generated file, panics if it is already generated from another source
+fn claim_output(
+    outputs: &mut HashMap<camino::Utf8PathBuf, String>,
+    out: camino::Utf8PathBuf,
+    src: &str,
+) {
+    if let Some(prev) = outputs.get(&out) {
+        panic!(
+            "output path {} of {} is also generated by {}",
+            out, src, prev
+        );
+    }
+    outputs.insert(out, src.to_string());
+}
+
+fn build(matches: &clap::ArgMatches) {
+    use crate::ofmt::{
+        feed_content, render_breadcrumbs, write_article_page, write_feed, write_index,
+        write_redirects, write_search_index, write_search_page, ArticleLinks,
+    };
+    use crate::outfile::OutFile;
+    use crate::utils::*;
+
+    let null_path = Path::new("");
+    let root_dir = camino::Utf8Path::new("");
 
     let indir: &String = matches.get_one("INPUT_DIR").unwrap();
     let outdir: &String = matches.get_one("output_dir").unwrap();
-    std::fs::create_dir_all(&outdir).expect("unable to create output directory");
-
-    let (config, config_mtime): (Config, Option<_>) = {
-        let mut fh = File::open(matches.get_one::<String>("config").unwrap())
-            .expect("unable to open config file");
-        let config_mtime = fh
-            .metadata()
-            .expect("unable to get config file stat()")
-            .modified()
-            .ok();
-        let fh_data = readfilez::read_part_from_file(
-            &mut fh,
-            0,
-            readfilez::LengthSpec {
-                bound: None,
-                is_exact: true,
-            },
-        )
-        .expect("unable to read config file");
-        (
-            serde_yaml::from_slice(&*fh_data).expect("unable to decode file as YAML"),
-            config_mtime,
-        )
+    std::fs::create_dir_all(outdir).expect("unable to create output directory");
+
+    let (mut config, mut config_mtime) =
+        load_config(Path::new(matches.get_one::<String>("config").unwrap()))
+            .expect("unable to load config file");
+
+    let indir = Path::new(indir);
+    let outdir = Path::new(outdir);
+
+    // directory metadata, by directory relative to the input directory
+    let mut dirmetas = BTreeMap::<camino::Utf8PathBuf, DirMeta>::new();
+    // source paths of all posts which have translations
+    let mut translated = HashSet::<String>::new();
+    // output directories which get an index page, because they contain listed posts
+    let mut index_dirs = BTreeSet::<camino::Utf8PathBuf>::new();
+    for dirent in walkdir::WalkDir::new(indir)
+        .into_iter()
+        .filter_entry(is_not_hidden)
+    {
+        let dirent = match dirent {
+            Ok(x) if x.file_type().is_file() => x,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("walkdir error: {}", e);
+                continue;
+            }
+        };
+        if dirent.file_name() != DIR_META_FILE {
+            // only the front matter is needed here, errors are reported by the main walk
+            let post = std::fs::read_to_string(dirent.path())
+                .ok()
+                .and_then(|data| {
+                    let data = fmatter::normalize_newlines(&data);
+                    let (fm, _) = fmatter::split(&data).ok()?;
+                    fm.parse::<Post>().ok()
+                });
+            let srcrel = dirent
+                .path()
+                .strip_prefix(indir)
+                .ok()
+                .and_then(camino::Utf8Path::from_path);
+            if let (Some(post), Some(srcrel)) = (post, srcrel) {
+                if let Some(x) = &post.translation_of {
+                    translated.insert(x.trim_start_matches('/').to_string());
+                }
+                let cdate = yz_diary_date::parse_from_utf8path(srcrel)
+                    .filter(|_| post.typ != PostTyp::Page && !post.unlisted);
+                if let Some(fpap) = cdate
+                    .and_then(|cdate| output_rel_path(&config, srcrel, &post, Some(cdate)).ok())
+                {
+                    index_dirs.extend(fpap.ancestors().skip(1).map(camino::Utf8Path::to_path_buf));
+                }
+            }
+            continue;
+        }
+        let meta = load_dir_meta(dirent.path())
+            .unwrap_or_else(|e| panic!("unable to load {}: {}", dirent.path().display(), e));
+        let dir: &camino::Utf8Path = dirent
+            .path()
+            .parent()
+            .unwrap()
+            .strip_prefix(indir)
+            .expect("unable to strip path prefix")
+            .try_into()
+            .expect("got invalid directory name");
+        dirmetas.insert(dir.to_path_buf(), meta);
+    }
+    // the metadata of the input directory itself applies to everything
+    if let Some(meta) = dirmetas.get(root_dir) {
+        meta.apply(&mut config);
+        config_mtime = config_mtime.max(meta.mtime);
+    }
+    let dir_order = |dir: &camino::Utf8Path| {
+        dirmetas
+            .get(dir)
+            .and_then(|i| i.sort)
+            .or_else(|| config.index_order.get(dir.as_str()).copied())
+            .unwrap_or_default()
     };
 
+    let mangler = Mangler::with_config(&config.mangler);
+
     let mut mainidx = Index {
-        typ: IndexTyp::Directory,
-        oidxrefs: Vec::new(),
-        ents: Vec::new(),
+        order: dir_order(root_dir),
+        description: dirmetas
+            .get(root_dir)
+            .map(|i| i.description.clone())
+            .unwrap_or_default(),
+        ..Default::default()
     };
-    let mut tagents = HashMap::<_, Vec<_>>::new();
-    let mut subents = HashMap::<_, Index>::new();
+    // ordered maps, to keep the output deterministic
+    let mut tagents = BTreeMap::<String, (String, Vec<_>)>::new();
+    let mut authorents = BTreeMap::<String, Vec<_>>::new();
+    let mut seriesents = BTreeMap::<String, Series>::new();
+    // article pages which depend on other posts (series and translations),
+    // rendered after all posts are known
+    let mut deferred = Vec::new();
+    let mut subents = BTreeMap::<_, Index>::new();
+    let mut searchents = Vec::new();
+    // indexes of posts which aren't in the default language
+    let mut langents = BTreeMap::<String, Index>::new();
+    // (language, href) of all translations of a post, by the source path of the original
+    let mut translations = BTreeMap::<String, Vec<(String, String)>>::new();
+    // (alias, target, source)
+    let mut aliases = Vec::new();
+    // all generated files and their source, used to detect collisions
+    let mut outputs = HashMap::<camino::Utf8PathBuf, String>::new();
 
     let force_rebuild = matches.get_flag("force-rebuild");
     let mut crds = HashSet::new();
-    let indir = Path::new(indir);
-    let outdir = Path::new(outdir);
+
+    // renders an article page, returns whether the output file changed
+    let render_article = |config: &Config,
+                          fpap: &camino::Utf8Path,
+                          cdate: Option<chrono::NaiveDate>,
+                          rd: &Post,
+                          content: &str,
+                          links: &ArticleLinks<'_>| {
+        let outfilp = outdir.join(fpap);
+        let mut wr = OutFile::new(&outfilp, config.skip_unchanged);
+        write_article_page(
+            &mangler,
+            config,
+            fpap.as_ref(),
+            cdate,
+            &mut wr,
+            rd,
+            content,
+            links,
+        )
+        .and_then(|()| wr.commit())
+        .unwrap_or_else(|x| {
+            panic!(
+                "got error from write_article_page (src = {}, dst = {}): {:?}",
+                fpap.as_str(),
+                outfilp.display(),
+                x
+            )
+        })
+    };
 
     for dirent in walkdir::WalkDir::new(indir)
+        .sort_by_file_name()
         .into_iter()
         // skip directories like .git
         .filter_entry(is_not_hidden)
@@ -137,7 +361,7 @@ fn main() {
                 continue;
             }
         };
-        if fh_meta.is_dir() {
+        if fh_meta.is_dir() || dirent.file_name() == DIR_META_FILE {
             continue;
         }
         let fh_data = match readfilez::read_from_file(File::open(dirent.path())) {
@@ -152,13 +376,32 @@ fn main() {
             }
         };
 
-        let fpap = dirent
+        let srcrel: &camino::Utf8Path = dirent
             .path()
             .strip_prefix(indir)
             .expect("unable to strip path prefix")
-            .with_extension("html");
+            .try_into()
+            .expect("got invalid file name");
+        let fh_data: &str = std::str::from_utf8(&fh_data).expect("file doesn't contain UTF-8");
+        let fh_data = fmatter::normalize_newlines(fh_data);
+        let (mut rd, content) = parse_post(dirent.path(), &fh_data);
+        // the diary date always comes from the source path, pages don't have one
+        let cdate = match rd.typ {
+            PostTyp::Page => None,
+            _ => Some(
+                yz_diary_date::parse_from_utf8path(srcrel)
+                    .expect("file name without parsable diary date"),
+            ),
+        };
+
+        let fpap = output_rel_path(&config, srcrel, &rd, cdate).unwrap_or_else(|e| {
+            panic!(
+                "unable to determine output path of {}: {}",
+                dirent.path().display(),
+                e
+            )
+        });
         let outfilp = outdir.join(&fpap);
-        let fpap: camino::Utf8PathBuf = fpap.try_into().expect("got invalid file name");
         if let Some(x) = outfilp.parent() {
             if !crds.contains(x) {
                 std::fs::create_dir_all(x).expect("unable to create destination directory");
@@ -166,99 +409,207 @@ fn main() {
             }
         }
         print!("- {}", fpap.as_str());
-        let fh_data: &str = std::str::from_utf8(&*fh_data).expect("file doesn't contain UTF-8");
-        let fh_data_spl = fh_data.find("\n---\n").expect("unable to get file header");
-        let mut rd: Post =
-            serde_yaml::from_str(&fh_data[..=fh_data_spl]).expect("unable to decode file as YAML");
-        let content = &fh_data[fh_data_spl + 5..];
-        let cdate = yz_diary_date::parse_from_utf8path(&fpap)
-            .expect("file name without parsable diary date");
 
         let fparent = fpap
             .parent()
             .and_then(|x| if x == null_path { None } else { Some(x) });
 
-        let (lnk, is_rel): (std::borrow::Cow<str>, bool) = match &rd.typ {
-            PostTyp::Link => {
-                let lnk = content.trim();
-                if !(lnk.starts_with('/') || lnk.contains("://")) {
-                    // relative URL, we need to prefix it with fparent
-                    (
-                        if let Some(x) = fparent {
-                            format!("{}/{}", x.as_str(), lnk).into()
-                        } else {
-                            lnk.into()
-                        },
-                        true,
-                    )
-                } else {
-                    (lnk.into(), false)
-                }
-            }
-            PostTyp::Text => {
-                let mut do_build = true;
-                if !force_rebuild {
-                    if let Some(config_mtime) = config_mtime {
-                        if let Ok(dst_meta) = std::fs::metadata(&outfilp) {
-                            if let Ok(src_mtime) = fh_meta.modified() {
-                                if let Ok(dst_mtime) = dst_meta.modified() {
-                                    if dst_mtime.duration_since(config_mtime).is_ok()
-                                        && dst_mtime.duration_since(src_mtime).is_ok()
-                                    {
-                                        // (config_mtime <= dst_mtime) && (src_mtime <= dst_mtime)
-                                        // source file, config, etc. wasn't modified since destination file was generated
-                                        print!(" [rebuild skipped]");
-                                        do_build = false;
-                                    }
+        let srcdir = srcrel.parent().unwrap_or(root_dir);
+        let (dcfg, dir_mtime) = dir_config(&config, &dirmetas, srcdir);
+        let has_page = rd.has_page(content);
+        if has_page {
+            claim_output(&mut outputs, fpap.clone(), srcrel.as_str());
+        }
+        let lang = rd.lang(&config).to_string();
+        if !is_valid_tag(&lang) {
+            eprintln!("   - got invalid language: {}", lang);
+        }
+        let tkey = match &rd.translation_of {
+            Some(x) => x.trim_start_matches('/').to_string(),
+            None => srcrel.to_string(),
+        };
+        if has_page {
+            translations
+                .entry(tkey.clone())
+                .or_default()
+                .push((lang.clone(), fpap.to_string()));
+            let config_mtime = config_mtime.map(|i| dir_mtime.map_or(i, |j| i.max(j)));
+            // the series navigation and language switcher depend on other posts
+            let needs_others = rd.series.is_some()
+                || rd.translation_of.is_some()
+                || translated.contains(srcrel.as_str());
+            let mut do_build = true;
+            if !force_rebuild && !needs_others {
+                if let Some(config_mtime) = config_mtime {
+                    if let Ok(dst_meta) = std::fs::metadata(&outfilp) {
+                        if let Ok(src_mtime) = fh_meta.modified() {
+                            if let Ok(dst_mtime) = dst_meta.modified() {
+                                if dst_mtime.duration_since(config_mtime).is_ok()
+                                    && dst_mtime.duration_since(src_mtime).is_ok()
+                                {
+                                    // (config_mtime <= dst_mtime) && (src_mtime <= dst_mtime)
+                                    // source file, config, etc. wasn't modified since destination file was generated
+                                    print!(" [rebuild skipped]");
+                                    do_build = false;
                                 }
                             }
                         }
                     }
                 }
-                if do_build {
-                    let fhout =
-                        std::fs::File::create(&outfilp).expect("unable to open output file");
-                    let wr = std::io::BufWriter::new(fhout);
-                    if let Err(x) =
-                        write_article_page(&mangler, &config, fpap.as_ref(), wr, &rd, content)
-                    {
-                        std::fs::remove_file(&outfilp)
-                            .expect("unable to remove corrupted output file");
-                        panic!(
-                            "got error from write_article_page (src = {}, dst = {}): {:?}",
-                            fpap.as_str(),
-                            outfilp.display(),
-                            x
-                        );
+            }
+            if do_build && needs_others {
+                deferred.push((
+                    dirent.path().to_path_buf(),
+                    srcdir.to_path_buf(),
+                    fpap.clone(),
+                    cdate,
+                    tkey,
+                ));
+                print!(" [deferred]");
+            } else if do_build {
+                let links = ArticleLinks {
+                    breadcrumbs: render_breadcrumbs(
+                        &dirmetas,
+                        &index_dirs,
+                        fparent.unwrap_or(root_dir),
+                        false,
+                    ),
+                    ..Default::default()
+                };
+                if !render_article(&dcfg, &fpap, cdate, &rd, content, &links) {
+                    print!(" [unchanged]");
+                }
+            }
+        }
+        if has_page {
+            if let (true, false, Some(cdate)) = (config.search.enabled, rd.unlisted, cdate) {
+                searchents.push(SearchEntry::new(
+                    &config.search,
+                    &mangler,
+                    &rd,
+                    cdate,
+                    fpap.as_str(),
+                    content,
+                ));
+            }
+        }
+
+        let (lnk, is_rel): (std::borrow::Cow<str>, bool) =
+            match rd.link_target(content).map(str::to_string) {
+                Some(lnk) => {
+                    if !is_absolute_href(&lnk) {
+                        // relative URL, we need to prefix it with fparent
+                        (
+                            if let Some(x) = fparent {
+                                format!("{}/{}", x.as_str(), lnk).into()
+                            } else {
+                                lnk.into()
+                            },
+                            true,
+                        )
+                    } else {
+                        (lnk.into(), false)
                     }
                 }
-                (fpap.as_str().into(), true)
+                None => (fpap.as_str().into(), true),
+            };
+        let page = if has_page { Some(fpap.as_str()) } else { None };
+        for i in &rd.aliases {
+            let target = page.unwrap_or(&lnk).to_string();
+            aliases.push((i.clone(), target, fpap.to_string()));
+        }
+        let cdate = match cdate {
+            Some(x) if !rd.unlisted => x,
+            Some(_) => {
+                println!(" [unlisted]");
+                continue;
+            }
+            None => {
+                println!(" [page]");
+                continue;
             }
         };
         println!();
-        let idxent = IndexEntry::with_post_and_etc(&rd, cdate, &lnk);
+        let mut idxent = IndexEntry::with_post_and_etc(&rd, cdate, &lnk);
+        if rd.typ == PostTyp::Link {
+            idxent.page = page.map(str::to_string);
+        }
+        if rd.is_untitled_typ() || (rd.typ == PostTyp::Link && has_page) {
+            idxent.content = Some(feed_content(&mangler, &config, fpap.as_ref(), &rd, content));
+        }
+        let mut post_tags = HashSet::new();
         for i in std::mem::take(&mut rd.tags) {
-            if is_valid_tag(&i) {
-                tagents.entry(i).or_default().push(idxent.clone());
-            } else {
+            let slug = slugify(&i);
+            if !is_valid_tag(&slug) {
                 eprintln!("   - got invalid tag: {}", i);
+            } else if post_tags.insert(slug.clone()) {
+                // tags which collapse to the same slug are merged,
+                // the first spelling we encounter is used for display
+                tagents
+                    .entry(slug)
+                    .or_insert_with(|| (i, Vec::new()))
+                    .1
+                    .push(idxent.clone());
+            }
+        }
+        if let Some(series) = &rd.series {
+            let slug = slugify(series.name());
+            if !is_valid_tag(&slug) {
+                eprintln!("   - got invalid series name: {}", series.name());
+            } else {
+                let parts = &mut seriesents
+                    .entry(slug)
+                    .or_insert_with(|| Series {
+                        name: series.name().to_string(),
+                        parts: Vec::new(),
+                    })
+                    .parts;
+                if let Some(part) = series.part() {
+                    if parts.iter().any(|i| i.0 == Some(part)) {
+                        eprintln!("   - duplicate part {} of series {}", part, series.name());
+                    }
+                }
+                parts.push((series.part(), idxent.clone()));
+            }
+        }
+        for i in &idxent.authors {
+            if config.authors.contains_key(i) && is_valid_tag(i) {
+                authorents
+                    .entry(i.clone())
+                    .or_default()
+                    .push(idxent.clone());
             }
         }
-        mainidx.ents.push(idxent);
+        if lang == config.lang || !is_valid_tag(&lang) {
+            mainidx.ents.push(idxent);
+        } else {
+            langents
+                .entry(lang)
+                .or_insert_with(|| Index {
+                    typ: IndexTyp::Language,
+                    ..Default::default()
+                })
+                .ents
+                .push(idxent);
+        }
         if let Some(x) = fparent {
+            let mut subent = IndexEntry::with_post_and_etc(
+                &rd,
+                cdate,
+                if is_rel {
+                    camino::Utf8Path::new(&*lnk).file_name().unwrap()
+                } else {
+                    &lnk
+                },
+            );
+            if rd.typ == PostTyp::Link {
+                subent.page = fpap.file_name().filter(|_| has_page).map(str::to_string);
+            }
             subents
                 .entry(x.to_path_buf())
                 .or_default()
                 .ents
-                .push(IndexEntry::with_post_and_etc(
-                    &rd,
-                    cdate,
-                    if is_rel {
-                        camino::Utf8Path::new(&*lnk).file_name().unwrap()
-                    } else {
-                        &lnk
-                    },
-                ));
+                .push(subent);
         }
     }
 
@@ -270,6 +621,17 @@ fn main() {
     kv.sort_unstable();
     kv.dedup();
 
+    let site = SiteIndexes {
+        dirs: subents.keys().cloned().collect(),
+        langs: langents.keys().cloned().collect(),
+        tags: tagents.keys().cloned().collect(),
+        authors: authorents.keys().cloned().collect(),
+        series: seriesents.keys().cloned().collect(),
+    };
+    for (out, src) in site.outputs(&config) {
+        claim_output(&mut outputs, out, &src);
+    }
+
     for i in kv {
         if i == null_path {
             continue;
@@ -281,38 +643,186 @@ fn main() {
         }
         .oidxrefs
         .push(IndexRef {
-            name: i.file_name().unwrap().to_string(),
+            name: dir_title(&dirmetas, &i),
+            fname: i.file_name().unwrap().to_string(),
             typ: IndexTyp::Directory,
         });
     }
 
-    mainidx.oidxrefs.extend(tagents.keys().map(|i| IndexRef {
-        name: i.to_string(),
-        typ: IndexTyp::Tag,
-    }));
+    mainidx
+        .oidxrefs
+        .extend(tagents.iter().map(|(slug, (tag, _))| IndexRef {
+            name: tag.to_string(),
+            typ: IndexTyp::Tag,
+            fname: slug.to_string(),
+        }));
+    mainidx
+        .oidxrefs
+        .extend(authorents.keys().map(|id| IndexRef {
+            name: config.authors[id].name.clone(),
+            typ: IndexTyp::Author,
+            fname: format!("authors/{}", id),
+        }));
+    mainidx
+        .oidxrefs
+        .extend(seriesents.iter().map(|(slug, series)| IndexRef {
+            name: series.name.clone(),
+            typ: IndexTyp::Series,
+            fname: format!("series/{}", slug),
+        }));
+    mainidx
+        .oidxrefs
+        .extend(langents.keys().map(|lang| IndexRef {
+            name: lang_name(lang).to_string(),
+            typ: IndexTyp::Language,
+            fname: format!("index.{}", lang),
+        }));
 
     mainidx.prepare();
 
-    write_index(&config, outdir, "".as_ref(), &mainidx).expect("unable to write main-index");
-    write_feed(&config, outdir, &mainidx).expect("unable to write atom feed");
+    for i in seriesents.values_mut() {
+        i.prepare();
+    }
+    for i in translations.values_mut() {
+        i.sort_unstable();
+    }
+    for (srcpath, srcdir, fpap, cdate, tkey) in deferred {
+        print!("- {}", fpap.as_str());
+        let data = std::fs::read_to_string(&srcpath)
+            .unwrap_or_else(|e| panic!("unable to read {}: {}", srcpath.display(), e));
+        let data = fmatter::normalize_newlines(&data);
+        let (rd, content) = parse_post(&srcpath, &data);
+        let slug = rd.series.as_ref().map(|i| slugify(i.name()));
+        let links = ArticleLinks {
+            series: slug
+                .as_ref()
+                .and_then(|slug| Some((slug.as_str(), seriesents.get(slug)?))),
+            breadcrumbs: render_breadcrumbs(
+                &dirmetas,
+                &index_dirs,
+                fpap.parent().unwrap_or(root_dir),
+                false,
+            ),
+            translations: match translations.get(&tkey) {
+                Some(x) if x.len() > 1 => x.clone(),
+                _ => Vec::new(),
+            },
+        };
+        let (dcfg, _) = dir_config(&config, &dirmetas, &srcdir);
+        if !render_article(&dcfg, &fpap, cdate, &rd, content, &links) {
+            print!(" [unchanged]");
+        }
+        println!();
+    }
+
+    write_index(&config, outdir, "".as_ref(), "", &mainidx).expect("unable to write main-index");
+    write_feed(&config, outdir, &mainidx, None).expect("unable to write atom feed");
+    for (lang, mut idx) in langents {
+        idx.prepare();
+        write_index(&config, outdir, lang.as_ref(), lang_name(&lang), &idx)
+            .expect("unable to write language index");
+        write_feed(&config, outdir, &idx, Some(&lang)).expect("unable to write atom feed");
+    }
 
+    let mut redirects = BTreeMap::new();
+    let config_redirects = config
+        .redirects
+        .iter()
+        .map(|(from, to)| (from.clone(), to.clone(), "config".to_string()));
+    for (from, to, src) in config_redirects.chain(aliases) {
+        match normalize_alias(&from) {
+            Ok(x) if outputs.contains_key(&x) => {
+                eprintln!(
+                    "   - alias {} (from {}) collides with a generated file",
+                    x, src
+                )
+            }
+            Ok(x) if redirects.contains_key(&x) => {
+                eprintln!("   - alias {} (from {}) is already defined", x, src)
+            }
+            Ok(x) => {
+                redirects.insert(x, to);
+            }
+            Err(e) => eprintln!("   - {} (from {})", e, src),
+        }
+    }
+    write_redirects(&config, outdir, &redirects).expect("unable to write redirects");
+
+    if config.search.enabled {
+        searchents.sort_unstable_by(|a, b| b.date.cmp(&a.date).then_with(|| a.href.cmp(&b.href)));
+        write_search_index(&config, outdir, &searchents).expect("unable to write search index");
+        if config.search.page {
+            write_search_page(&config, outdir).expect("unable to write search page");
+        }
+    }
+
+    // directory metadata is looked up by the output directory,
+    // which matches the input directory unless `permalink` is used
     for (subdir, mut p_ents) in subents.into_iter() {
+        let meta = dirmetas.get(&subdir);
+        p_ents.order = dir_order(&subdir);
+        p_ents.breadcrumbs = render_breadcrumbs(&dirmetas, &index_dirs, &subdir, true);
+        if let Some(meta) = meta {
+            p_ents.description.clone_from(&meta.description);
+        }
         p_ents.prepare();
-        write_index(&config, outdir, subdir.as_ref(), &p_ents).expect("unable to write sub-index");
+        let title = meta
+            .and_then(|i| i.title.as_deref())
+            .unwrap_or(subdir.as_str());
+        let (dcfg, _) = dir_config(&config, &dirmetas, &subdir);
+        write_index(&dcfg, outdir, subdir.as_ref(), title, &p_ents)
+            .expect("unable to write sub-index");
     }
 
-    for (tag, mut p_ents) in tagents.into_iter() {
-        p_ents.sort_unstable();
+    for (slug, (tag, p_ents)) in tagents.into_iter() {
+        let mut idx = Index {
+            typ: IndexTyp::Tag,
+            ents: p_ents,
+            ..Default::default()
+        };
+        idx.prepare();
+        write_index(&config, outdir, slug.as_ref(), &tag, &idx).expect("unable to write tag-index");
+    }
+
+    if !authorents.is_empty() {
+        std::fs::create_dir_all(outdir.join("authors"))
+            .expect("unable to create authors directory");
+    }
+    if !seriesents.is_empty() {
+        std::fs::create_dir_all(outdir.join("series")).expect("unable to create series directory");
+    }
+    for (slug, series) in seriesents.into_iter() {
         write_index(
             &config,
             outdir,
-            tag.as_ref(),
+            Path::new("series").join(&slug).as_ref(),
+            &series.name,
+            // already in reading order
             &Index {
-                typ: IndexTyp::Tag,
-                oidxrefs: Vec::new(),
-                ents: p_ents,
+                typ: IndexTyp::Series,
+                ents: series.parts.into_iter().map(|(_, i)| i).collect(),
+                ..Default::default()
             },
         )
-        .expect("unable to write tag-index");
+        .expect("unable to write series-index");
+    }
+
+    for (id, p_ents) in authorents.into_iter() {
+        let prof = &config.authors[&id];
+        let mut idx = Index {
+            typ: IndexTyp::Author,
+            description: prof.bio.clone(),
+            ents: p_ents,
+            ..Default::default()
+        };
+        idx.prepare();
+        write_index(
+            &config,
+            outdir,
+            Path::new("authors").join(&id).as_ref(),
+            &prof.name,
+            &idx,
+        )
+        .expect("unable to write author-index");
     }
 }
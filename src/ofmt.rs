@@ -1,37 +1,464 @@
-use crate::utils::{back_to_idx, guess_text_type, Config, Index, IndexTyp, Mangler, Post};
+use crate::outfile::OutFile;
+use crate::toc::{add_heading_anchors, render_toc, TOC_MARKER};
+use crate::utils::{
+    absolute_url, back_to_idx, build_timestamp, dir_title, escape_html, guess_text_type,
+    is_absolute_href, lang_name, root_prefix, site_path, strip_html, url_domain, Config, DirMeta,
+    Index, IndexTyp, Mangler, Post, PostTyp, SearchEntry, Series,
+};
 use atom_syndication::Text;
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::naive::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{Result, Write};
 use std::path::Path;
 
 const OIDXREFS_LINE_MAXLEN: usize = 100;
 
+fn write_content<W: Write>(
+    mangler: &Mangler,
+    mut wr: W,
+    mangle: bool,
+    lang: &str,
+    content: &str,
+) -> Result<()> {
+    if !mangle {
+        // hand-written HTML, pass through as-is
+        write!(&mut wr, "<br />\n{}", content)?;
+        if !content.ends_with('\n') {
+            writeln!(&mut wr)?;
+        }
+        return Ok(());
+    }
+    let mut ret = String::with_capacity(content.len());
+    let mut it = mangler.mangle_content(content);
+    if let Some((do_mangle, i)) = it.next() {
+        ret += if do_mangle { "\n    " } else { "<br />\n" };
+        ret += &i;
+        ret.push('\n');
+    }
+    for (do_mangle, i) in it {
+        if do_mangle {
+            ret += "    ";
+        }
+        ret += &i;
+        ret.push('\n');
+    }
+    write!(&mut wr, "{}", mangler.typeset(&ret, lang))
+}
+
+/// writes the post body, wrapped according to the post type;
+/// `img_src` is the (possibly absolutized) image URL of image posts
+fn write_body<W: Write>(
+    mangler: &Mangler,
+    mut wr: W,
+    rd: &Post,
+    lang: &str,
+    img_src: &str,
+    content: &str,
+) -> Result<()> {
+    match rd.typ {
+        PostTyp::Quote => {
+            write!(&mut wr, "    <blockquote class=\"e-content\"")?;
+            if !rd.cite.is_empty() {
+                write!(&mut wr, " cite=\"{}\"", escape_html(&rd.cite))?;
+            }
+            write!(&mut wr, ">")?;
+            write_content(mangler, &mut wr, rd.mangle, lang, content)?;
+            writeln!(&mut wr, "    </blockquote>")?;
+            if !rd.source.is_empty() {
+                let source = if rd.cite.is_empty() {
+                    rd.source.clone()
+                } else {
+                    format!("<a href=\"{}\">{}</a>", escape_html(&rd.cite), rd.source)
+                };
+                writeln!(
+                    &mut wr,
+                    "    <p class=\"quote-source\">&mdash; <cite>{}</cite></p>",
+                    source
+                )?;
+            }
+        }
+        PostTyp::Image => {
+            writeln!(
+                &mut wr,
+                "    <figure>\n      <img class=\"u-photo\" src=\"{}\" alt=\"{}\" />",
+                escape_html(img_src),
+                escape_html(&rd.alt)
+            )?;
+            if !rd.caption.is_empty() {
+                writeln!(&mut wr, "      <figcaption>{}</figcaption>", rd.caption)?;
+            }
+            writeln!(&mut wr, "    </figure>")?;
+            // the content is an optional description
+            if !content.trim().is_empty() {
+                write!(&mut wr, "    <div class=\"e-content\">")?;
+                write_content(mangler, &mut wr, rd.mangle, lang, content)?;
+                writeln!(&mut wr, "    </div>")?;
+            }
+        }
+        _ => {
+            write!(&mut wr, "    <div class=\"e-content\">")?;
+            write_content(mangler, &mut wr, rd.mangle, lang, content)?;
+            writeln!(&mut wr, "    </div>")?;
+        }
+    }
+    if rd.typ == PostTyp::Link && !rd.via.is_empty() {
+        let via = rd.via.trim();
+        writeln!(
+            &mut wr,
+            "    <p class=\"via\">via <a href=\"{}\">{}</a></p>",
+            escape_html(via),
+            escape_html(url_domain(via).unwrap_or(via))
+        )?;
+    }
+    Ok(())
+}
+
+/// renders the body of a post for inclusion in the feed
+pub fn feed_content(
+    mangler: &Mangler,
+    config: &Config,
+    fpath: &Path,
+    rd: &Post,
+    content: &str,
+) -> String {
+    let img_src = absolute_url(config, fpath, &rd.src);
+    let mut ret = Vec::new();
+    write_body(mangler, &mut ret, rd, rd.lang(config), &img_src, content)
+        .expect("unable to render feed content");
+    String::from_utf8(ret).unwrap()
+}
+
+fn write_meta_tags<W: Write>(
+    mut wr: W,
+    config: &Config,
+    fpath: &Path,
+    cdate: Option<NaiveDate>,
+    rd: &Post,
+) -> Result<()> {
+    let meta = |wr: &mut W, attr: &str, key: &str, value: &str| {
+        writeln!(
+            wr,
+            "    <meta {}=\"{}\" content=\"{}\" />",
+            attr,
+            key,
+            escape_html(value)
+        )
+    };
+    let description = if rd.description.is_empty() {
+        &config.description
+    } else {
+        &rd.description
+    };
+    let image = if !rd.image.is_empty() {
+        &rd.image
+    } else if rd.typ == PostTyp::Image && !rd.src.is_empty() {
+        &rd.src
+    } else {
+        &config.image
+    };
+    let title = strip_html(&rd.title);
+    let url = absolute_url(config, fpath, fpath.file_name().unwrap().to_str().unwrap());
+
+    if !description.is_empty() {
+        meta(&mut wr, "name", "description", description)?;
+    }
+    meta(&mut wr, "property", "og:title", &title)?;
+    meta(
+        &mut wr,
+        "property",
+        "og:type",
+        if cdate.is_some() {
+            "article"
+        } else {
+            "website"
+        },
+    )?;
+    meta(&mut wr, "property", "og:url", &url)?;
+    meta(&mut wr, "property", "og:site_name", &config.blog_name)?;
+    if !description.is_empty() {
+        meta(&mut wr, "property", "og:description", description)?;
+    }
+    if let Some(cdate) = cdate {
+        meta(
+            &mut wr,
+            "property",
+            "article:published_time",
+            &cdate.format("%Y-%m-%d").to_string(),
+        )?;
+    }
+    if let Some(updated) = rd.last_updated() {
+        meta(
+            &mut wr,
+            "property",
+            "article:modified_time",
+            &updated.format("%Y-%m-%d").to_string(),
+        )?;
+    }
+    for i in &rd.tags {
+        meta(&mut wr, "property", "article:tag", i)?;
+    }
+    if !image.is_empty() {
+        meta(
+            &mut wr,
+            "property",
+            "og:image",
+            &absolute_url(config, fpath, image),
+        )?;
+    }
+    meta(
+        &mut wr,
+        "name",
+        "twitter:card",
+        if image.is_empty() {
+            "summary"
+        } else {
+            "summary_large_image"
+        },
+    )?;
+    if !config.twitter_site.is_empty() {
+        meta(&mut wr, "name", "twitter:site", &config.twitter_site)?;
+    }
+    Ok(())
+}
+
+fn write_json_ld<W: Write>(
+    mut wr: W,
+    config: &Config,
+    fpath: &Path,
+    cdate: Option<NaiveDate>,
+    rd: &Post,
+) -> Result<()> {
+    use serde_json::{json, Value};
+
+    let person = |id: &str| match config.authors.get(id) {
+        Some(prof) => {
+            let mut p = json!({ "@type": "Person", "name": prof.name });
+            if let Some(uri) = &prof.uri {
+                p["url"] = uri.clone().into();
+            }
+            p
+        }
+        None => json!({ "@type": "Person", "name": id }),
+    };
+    let mut authors: Vec<Value> = rd.author_ids().iter().map(|i| person(i)).collect();
+    if authors.is_empty() {
+        authors.push(person(&config.author));
+    }
+    let url = absolute_url(config, fpath, fpath.file_name().unwrap().to_str().unwrap());
+
+    let mut data = json!({
+        "@context": "https://schema.org",
+        "@type": if cdate.is_some() { "BlogPosting" } else { "WebPage" },
+        "headline": strip_html(&rd.title),
+        "inLanguage": rd.lang(config),
+        "author": authors,
+        "url": url,
+        "mainEntityOfPage": url,
+        "publisher": { "@type": "Organization", "name": config.blog_name },
+    });
+    if let Some(cdate) = cdate {
+        data["datePublished"] = cdate.format("%Y-%m-%d").to_string().into();
+    }
+    if let Some(updated) = rd.last_updated() {
+        data["dateModified"] = updated.format("%Y-%m-%d").to_string().into();
+    }
+    if !rd.tags.is_empty() {
+        data["keywords"] = rd.tags.join(", ").into();
+    }
+    if !rd.description.is_empty() {
+        data["description"] = rd.description.clone().into();
+    }
+    if !rd.image.is_empty() {
+        data["image"] = absolute_url(config, fpath, &rd.image).into();
+    }
+
+    // `</` would terminate the script element early
+    let data = serde_json::to_string(&data)
+        .expect("unable to serialize JSON-LD")
+        .replace("</", "<\\/");
+    writeln!(
+        &mut wr,
+        "    <script type=\"application/ld+json\">{}</script>",
+        data
+    )
+}
+
+/// information about related posts, shown on an article page
+#[derive(Default)]
+pub struct ArticleLinks<'a> {
+    // slug and contents of the series the post is part of
+    pub series: Option<(&'a str, &'a Series)>,
+    // HTML, see `render_breadcrumbs`
+    pub breadcrumbs: String,
+    // language and href (relative to the root) of all translations, including the post itself
+    pub translations: Vec<(String, String)>,
+}
+
+fn render_lang_switcher(fpath: &Path, cur: &str, translations: &[(String, String)]) -> String {
+    if translations.len() < 2 {
+        return String::new();
+    }
+    let root = root_prefix(fpath);
+    let mut ret = String::from("    <nav class=\"translations\">");
+    for (n, (lang, href)) in translations.iter().enumerate() {
+        if n != 0 {
+            ret += " | ";
+        }
+        if lang == cur {
+            ret += &format!("<span aria-current=\"page\">{}</span>", lang_name(lang));
+        } else {
+            ret += &format!(
+                "<a href=\"{}{}\" hreflang=\"{}\" lang=\"{}\">{}</a>",
+                root,
+                href,
+                escape_html(lang),
+                escape_html(lang),
+                lang_name(lang)
+            );
+        }
+    }
+    ret += "</nav>\n";
+    ret
+}
+
+/// renders links to the main index and all ancestor directories of `dir`
+/// which have an index page (titled via their metadata),
+/// or nothing for the root directory;
+/// `is_index` is set for the index page of `dir` itself
+pub fn render_breadcrumbs(
+    dirmetas: &BTreeMap<Utf8PathBuf, DirMeta>,
+    index_dirs: &BTreeSet<Utf8PathBuf>,
+    dir: &Utf8Path,
+    is_index: bool,
+) -> String {
+    let root = "../".repeat(dir.components().count());
+    let mut ancestors: Vec<_> = dir
+        .ancestors()
+        .filter(|i| !i.as_str().is_empty() && index_dirs.contains(*i))
+        .map(|i| (i, dir_title(dirmetas, i)))
+        .collect();
+    if ancestors.is_empty() {
+        return String::new();
+    }
+    ancestors.reverse();
+    let mut ret = format!(
+        "<nav class=\"breadcrumbs\"><a href=\"{}index.html\">Hauptseite</a>",
+        root
+    );
+    for (i, title) in ancestors {
+        if is_index && i == dir {
+            ret += &format!(" &rsaquo; <span aria-current=\"page\">{}</span>", title);
+        } else {
+            ret += &format!(
+                " &rsaquo; <a href=\"{}{}/index.html\">{}</a>",
+                root, i, title
+            );
+        }
+    }
+    ret += "</nav>";
+    ret
+}
+
+fn write_series_nav<W: Write>(mut wr: W, fpath: &Path, slug: &str, series: &Series) -> Result<()> {
+    let root = root_prefix(fpath);
+    let cur = fpath.to_str().unwrap();
+    let pos = series.parts.iter().position(|(_, i)| i.href == cur);
+    write!(&mut wr, "    <nav class=\"series\">\n    <p>")?;
+    if let Some(pos) = pos {
+        write!(
+            &mut wr,
+            "Teil {} von {} der Serie ",
+            pos + 1,
+            series.parts.len()
+        )?;
+    } else {
+        write!(&mut wr, "Serie: ")?;
+    }
+    writeln!(
+        &mut wr,
+        "<a href=\"{}series/{}.html\">{}</a></p>\n    <ol>",
+        root, slug, series.name
+    )?;
+    for (n, (_, i)) in series.parts.iter().enumerate() {
+        if Some(n) == pos {
+            writeln!(&mut wr, "      <li class=\"current\">{}</li>", i.title)?;
+        } else {
+            let href_prefix = if is_absolute_href(&i.href) {
+                ""
+            } else {
+                &*root
+            };
+            writeln!(
+                &mut wr,
+                "      <li><a href=\"{}{}\">{}</a></li>",
+                href_prefix, i.href, i.title
+            )?;
+        }
+    }
+    writeln!(&mut wr, "    </ol>\n    </nav>")
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn write_article_page<W: Write>(
     mangler: &Mangler,
     config: &Config,
     fpath: &Path,
+    cdate: Option<NaiveDate>,
     mut wr: W,
     rd: &Post,
     content: &str,
+    links: &ArticleLinks<'_>,
 ) -> Result<()> {
+    let lang = rd.lang(config);
     writeln!(
         &mut wr,
         r##"<!doctype html>
-<html lang="de" dir="ltr">
+<html lang="{}" dir="ltr">
   <head>
     <meta charset="utf-8" />
     <meta name="viewport" content="width=device-width, initial-scale=1.0" />
     <link rel="stylesheet" href="{}" type="text/css" />
-    <title>{} &mdash; {}</title>
-{}{}  </head>
-  <body>
-    <h1>{}</h1>
-{}    <a href="#" onclick="window.history.back()">Zur&uuml;ck zur vorherigen Seite</a> - <a href="{}">Zur&uuml;ck zur Hauptseite</a>{}"##,
+    <title>{} &mdash; {}</title>"##,
+        escape_html(lang),
         config.stylesheet,
         rd.title,
         config.blog_name,
+    )?;
+    for (lang, href) in &links.translations {
+        writeln!(
+            &mut wr,
+            "    <link rel=\"alternate\" hreflang=\"{}\" href=\"{}\" />",
+            escape_html(lang),
+            escape_html(&absolute_url(config, Path::new(""), href))
+        )?;
+    }
+    write_meta_tags(&mut wr, config, fpath, cdate, rd)?;
+    write_json_ld(&mut wr, config, fpath, cdate, rd)?;
+    write!(
+        &mut wr,
+        r##"{}{}  </head>
+  <body>
+{}    <article class="h-entry">
+{}{}{}    <a href="#" onclick="window.history.back()">Zur&uuml;ck zur vorherigen Seite</a> - <a href="{}">Zur&uuml;ck zur Hauptseite</a>{}"##,
         config.x_head,
         rd.x_head,
-        rd.title,
+        if links.breadcrumbs.is_empty() {
+            String::new()
+        } else {
+            format!("    {}\n", links.breadcrumbs)
+        },
+        render_lang_switcher(fpath, lang, &links.translations),
+        // notes don't have a visible title, the title of link posts links to the target
+        match rd.typ {
+            PostTyp::Note => String::new(),
+            PostTyp::Link => format!(
+                "    <h1 class=\"p-name\"><a class=\"u-bookmark-of\" href=\"{}\">{}</a></h1>\n",
+                escape_html(rd.url.trim()),
+                rd.title
+            ),
+            _ => format!("    <h1 class=\"p-name\">{}</h1>\n", rd.title),
+        },
         config.x_body_ph1,
         back_to_idx(fpath),
         config.x_nav,
@@ -39,26 +466,68 @@ pub fn write_article_page<W: Write>(
     if !rd.x_nav.is_empty() {
         write!(&mut wr, " - {}", rd.x_nav)?;
     }
-    write!(&mut wr, "<br />")?;
-    let mut it = mangler.mangle_content(content);
-    if let Some((do_mangle, i)) = it.next() {
-        if do_mangle {
-            write!(&mut wr, "\n    ")
-        } else {
-            writeln!(&mut wr, "<br />")
-        }?;
-        writeln!(&mut wr, "{}", i)?;
+    if let Some(cdate) = cdate {
+        write!(
+            &mut wr,
+            "<br />\n    <time class=\"dt-published\" datetime=\"{}\">{}</time>",
+            cdate.format("%Y-%m-%d"),
+            cdate.format("%d.%m.%Y"),
+        )?;
     }
-    for (do_mangle, i) in it {
-        if do_mangle {
-            write!(&mut wr, "    ")?;
+    if let Some(updated) = rd.last_updated() {
+        if cdate.is_none() {
+            write!(&mut wr, "<br />\n   ")?;
         }
-        writeln!(&mut wr, "{}", i)?;
+        write!(
+            &mut wr,
+            " (zuletzt aktualisiert: <time class=\"dt-updated\" datetime=\"{}\">{}</time>)",
+            updated.format("%Y-%m-%d"),
+            updated.format("%d.%m.%Y"),
+        )?;
     }
-    if !rd.author.is_empty() {
-        writeln!(&mut wr, "    <p>Autor: {}</p>", rd.author)?;
+    writeln!(&mut wr)?;
+    if let Some((slug, series)) = links.series {
+        write_series_nav(&mut wr, fpath, slug, series)?;
     }
-    writeln!(&mut wr, "  </body>\n</html>")?;
+    let (content, headings) = add_heading_anchors(content, config.heading_self_links);
+    let content = if rd.toc && !headings.is_empty() {
+        let toc = render_toc(&headings);
+        if content.contains(TOC_MARKER) {
+            content.replacen(TOC_MARKER, &toc, 1)
+        } else {
+            format!("{}\n\n{}", toc, content)
+        }
+    } else {
+        content
+    };
+    write_body(mangler, &mut wr, rd, rd.lang(config), &rd.src, &content)?;
+    if !rd.changes.is_empty() {
+        let mut changes: Vec<_> = rd.changes.iter().collect();
+        changes.sort_by_key(|i| i.date);
+        writeln!(
+            &mut wr,
+            "    <h2>&Auml;nderungen</h2>\n    <ul class=\"changelog\">"
+        )?;
+        for i in changes {
+            writeln!(
+                &mut wr,
+                "      <li><time datetime=\"{}\">{}</time>: {}</li>",
+                i.date.format("%Y-%m-%d"),
+                i.date.format("%d.%m.%Y"),
+                i.note
+            )?;
+        }
+        writeln!(&mut wr, "    </ul>")?;
+    }
+    let author_ids = rd.author_ids();
+    if !author_ids.is_empty() {
+        writeln!(
+            &mut wr,
+            "    <p>Autor: {}</p>",
+            config.authors_html(&author_ids, &root_prefix(fpath))
+        )?;
+    }
+    writeln!(&mut wr, "    </article>\n  </body>\n</html>")?;
     wr.flush()?;
     Ok(())
 }
@@ -67,6 +536,7 @@ pub fn write_index(
     config: &Config,
     outdir: &Path,
     idx_name: &Path,
+    idx_title: &str,
     data: &Index,
 ) -> std::io::Result<()> {
     println!("- index: {}", idx_name.display());
@@ -85,16 +555,41 @@ pub fn write_index(
             fpath.set_extension("html");
             ("Tag: ", "<a href=\"index.html\">[Hauptseite]</a>")
         }
+        IndexTyp::Author => {
+            fpath.set_extension("html");
+            ("Autor: ", "<a href=\"../index.html\">[Hauptseite]</a>")
+        }
+        IndexTyp::Series => {
+            fpath.set_extension("html");
+            ("Serie: ", "<a href=\"../index.html\">[Hauptseite]</a>")
+        }
+        IndexTyp::Language => {
+            fpath = outdir.join(format!("index.{}.html", idx_name.display()));
+            ("Sprache: ", "<a href=\"index.html\">[Hauptseite]</a>")
+        }
+    };
+    // relative path to the root of the output directory
+    let root = match data.typ {
+        IndexTyp::Directory => "../".repeat(idx_name.components().count()),
+        IndexTyp::Tag | IndexTyp::Language => String::new(),
+        IndexTyp::Author | IndexTyp::Series => "../".to_string(),
     };
     let it_post = if it_pre.is_empty() { "" } else { " &mdash; " };
+    let (lang, feed) = match data.typ {
+        IndexTyp::Language => {
+            let lang = idx_name.to_str().unwrap();
+            (lang, Some(format!("feed.{}.atom", lang)))
+        }
+        _ if it_pre.is_empty() => (&*config.lang, Some("feed.atom".to_string())),
+        _ => (&*config.lang, None),
+    };
 
-    let mut f = std::io::BufWriter::new(std::fs::File::create(fpath)?);
-    let idx_name_s = idx_name.to_str().unwrap();
+    let mut f = OutFile::new(&fpath, config.skip_unchanged);
 
     write!(
         &mut f,
         r#"<!doctype html>
-<html lang="de" dir="ltr">
+<html lang="{}" dir="ltr">
   <head>
     <meta charset="utf-8" />
     <meta name="viewport" content="width=device-width, initial-scale=1.0" />
@@ -102,31 +597,38 @@ pub fn write_index(
 {}
     <title>{}{}{}{}</title>
 {}  </head>
-  <body>
-    <h1>{}{}{}{}</h1>
+  <body class="h-feed">
+    <h1 class="p-name">{}{}{}{}</h1>
 {}
 <tt>
 "#,
+        escape_html(lang),
         &config.stylesheet,
-        if it_pre.is_empty() {
-            r#"    <link rel="alternate" type="application/atom+xml" title="Atom feed" href="feed.atom" />
-"#
-        } else {
-            ""
+        match &feed {
+            Some(feed) => format!(
+                "    <link rel=\"alternate\" type=\"application/atom+xml\" title=\"Atom feed\" href=\"{}\" />\n",
+                feed
+            ),
+            None => String::new(),
         },
         it_pre,
-        idx_name_s,
+        idx_title,
         it_post,
         &config.blog_name,
         &config.x_head,
         it_pre,
-        idx_name_s,
+        idx_title,
         it_post,
         &config.blog_name,
         &config.x_body_ph1,
     )?;
 
-    if !up.is_empty() {
+    if !data.description.is_empty() {
+        writeln!(&mut f, "{}<br />", data.description)?;
+    }
+    if !data.breadcrumbs.is_empty() {
+        writeln!(&mut f, "{}<br />", data.breadcrumbs)?;
+    } else if !up.is_empty() {
         writeln!(&mut f, "{}<br />", up)?;
     }
 
@@ -148,7 +650,7 @@ pub fn write_index(
         write!(
             &mut refline,
             "<a href=\"{}{}.html\">{}</a>",
-            i.name.replace('&', "&amp;"),
+            i.fname.replace('&', "&amp;"),
             if i.typ == IndexTyp::Directory {
                 "/index"
             } else {
@@ -164,54 +666,135 @@ pub fn write_index(
         std::mem::drop(refline);
     }
 
-    for i in data.ents.iter().rev() {
+    // author and series index pages are in a subdirectory, but contain root-relative links
+    let root_if_rel = |href: &str| match data.typ {
+        IndexTyp::Author | IndexTyp::Series if !is_absolute_href(href) => root.clone(),
+        _ => String::new(),
+    };
+    for i in &data.ents {
+        let href_prefix = root_if_rel(&i.href);
+        let pin_pre = if i.pinned {
+            "<span class=\"pinned\">Angepinnt:</span> "
+        } else {
+            ""
+        };
+        let typ_pre = match i.typ {
+            PostTyp::Note => "<span class=\"posttyp\">Notiz:</span> ",
+            PostTyp::Quote => "<span class=\"posttyp\">Zitat:</span> ",
+            PostTyp::Image => "<span class=\"posttyp\">Bild:</span> ",
+            _ => "",
+        };
         write!(
             &mut f,
-            "{}: <a href=\"{}\">{}</a>",
+            "<span class=\"h-entry\">{}<time class=\"dt-published\" datetime=\"{}\">{}</time>: {}<a class=\"u-url p-name\"{} href=\"{}{}\">{}</a>",
+            pin_pre,
+            i.cdate.format("%Y-%m-%d"),
             i.cdate.format("%d.%m.%Y"),
+            typ_pre,
+            if i.typ == PostTyp::Link && url_domain(&i.href).is_some() {
+                " rel=\"external\""
+            } else {
+                ""
+            },
+            href_prefix,
             i.href,
             i.title
         )?;
-        if !i.author.is_empty() {
-            write!(&mut f, " <span class=\"authorspec\">by {}</span>", i.author)?;
+        if i.typ == PostTyp::Link {
+            if let Some(domain) = url_domain(&i.href) {
+                write!(
+                    &mut f,
+                    " <span class=\"domain\">({})</span> <span class=\"external\" title=\"externer Link\">&#8599;</span>",
+                    escape_html(domain)
+                )?;
+            }
+            if let Some(page) = &i.page {
+                write!(
+                    &mut f,
+                    " <a class=\"commentary\" href=\"{}{}\">[Kommentar]</a>",
+                    root_if_rel(page),
+                    page
+                )?;
+            }
+            if !i.via.is_empty() {
+                write!(
+                    &mut f,
+                    " <span class=\"via\">via <a href=\"{}\">{}</a></span>",
+                    escape_html(&i.via),
+                    escape_html(url_domain(&i.via).unwrap_or(&i.via))
+                )?;
+            }
         }
-        writeln!(&mut f, "<br />")?;
+        if !i.authors.is_empty() {
+            write!(
+                &mut f,
+                " <span class=\"authorspec\">by {}</span>",
+                config.authors_html(&i.authors, &root)
+            )?;
+        }
+        writeln!(&mut f, "</span><br />")?;
     }
 
     writeln!(&mut f, "</tt>\n  </body>\n</html>")?;
 
-    f.flush()?;
-    f.into_inner()?.sync_all()?;
+    f.commit()?;
     Ok(())
 }
 
-pub fn write_feed(config: &Config, outdir: &Path, data: &Index) -> std::io::Result<()> {
-    use atom_syndication::{Entry, Link, Person};
-    use chrono::{DateTime, TimeZone, Utc};
+fn atom_person(config: &Config, id: &str) -> atom_syndication::Person {
+    use atom_syndication::Person;
+    match config.authors.get(id) {
+        Some(prof) => Person {
+            name: prof.name.clone(),
+            email: prof.email.clone(),
+            uri: prof.uri.clone(),
+        },
+        None => Person {
+            name: id.to_string(),
+            email: None,
+            uri: None,
+        },
+    }
+}
 
-    assert_eq!(data.typ, IndexTyp::Directory);
-    println!("- atom feed");
+/// writes `feed.atom`, or `feed.{lang}.atom` for the index of another language
+pub fn write_feed(
+    config: &Config,
+    outdir: &Path,
+    data: &Index,
+    lang: Option<&str>,
+) -> std::io::Result<()> {
+    use atom_syndication::{Content, Entry, Link};
+    use chrono::{TimeZone, Utc};
+
+    let (fname, alternate) = match lang {
+        Some(lang) => (
+            format!("feed.{}.atom", lang),
+            format!("{}/index.{}.html", config.id, lang),
+        ),
+        None => ("feed.atom".to_string(), config.id.to_string()),
+    };
+    println!("- atom feed: {}", fname);
 
-    let now: DateTime<Utc> = Utc::now();
     let nult = chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap();
 
-    let feed = atom_syndication::Feed {
-        authors: vec![{
-            let mut p = Person::default();
-            p.set_name(&config.author);
-            p
-        }],
+    // newest first, regardless of the order of the index
+    let mut ents: Vec<_> = data.ents.iter().collect();
+    ents.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut feed = atom_syndication::Feed {
+        authors: vec![atom_person(config, &config.author)],
         links: vec![
             {
                 Link {
-                    href: config.id.as_str().to_string(),
+                    href: alternate.clone(),
                     rel: "alternate".to_string(),
                     ..Default::default()
                 }
             },
             {
                 Link {
-                    href: format!("{}/feed.atom", config.id),
+                    href: format!("{}/{}", config.id, fname),
                     rel: "self".to_string(),
                     ..Default::default()
                 }
@@ -223,34 +806,26 @@ pub fn write_feed(config: &Config, outdir: &Path, data: &Index) -> std::io::Resu
             lang: None,
             r#type: guess_text_type(&config.blog_name),
         },
-        id: config.id.to_string(),
-        entries: data
-            .ents
-            .iter()
-            .rev()
+        id: alternate,
+        lang: Some(lang.unwrap_or(&config.lang).to_string()),
+        entries: ents
+            .into_iter()
             .take(20)
             .map(|i| {
-                let (url, updts) = if i.href.starts_with('/') || i.href.contains("://") {
-                    // absolute link, use cdate as update timestamp
-                    (
-                        i.href.clone(),
-                        TimeZone::from_utc_datetime(&Utc, &i.cdate.and_time(nult)),
-                    )
-                } else {
-                    // relative link, use mtime, or use cdate as fallback
-                    (
-                        format!("{}/{}", config.id, i.href),
-                        match std::fs::metadata(outdir.join(&i.href)) {
-                            Ok(x) => crate::utils::system_time_to_date_time(x.modified().unwrap()),
-                            Err(e) => {
-                                eprintln!(
-                                    "  warning: unable to get mtime of: {}, error = {}",
-                                    i.href, e
-                                );
-                                TimeZone::from_utc_datetime(&Utc, &i.cdate.and_time(nult))
-                            }
-                        },
-                    )
+                // only use the content to derive timestamps, to keep builds reproducible
+                let abs = |href: &str| {
+                    if is_absolute_href(href) {
+                        href.to_string()
+                    } else {
+                        format!("{}/{}", config.id, href)
+                    }
+                };
+                // link posts with commentary are identified by their own page
+                let url = abs(i.page.as_ref().unwrap_or(&i.href));
+                let pubts = TimeZone::from_utc_datetime(&Utc, &i.cdate.and_time(nult));
+                let updts = match i.updated {
+                    Some(x) => TimeZone::from_utc_datetime(&Utc, &x.and_time(nult)),
+                    None => pubts,
                 };
                 Entry {
                     title: Text {
@@ -259,37 +834,263 @@ pub fn write_feed(config: &Config, outdir: &Path, data: &Index) -> std::io::Resu
                         lang: None,
                         r#type: guess_text_type(&i.title),
                     },
-                    id: url,
-                    links: vec![{
-                        Link {
-                            href: i.href.clone(),
-                            rel: "alternate".to_string(),
-                            ..Default::default()
-                        }
-                    }],
-                    authors: i
-                        .authors
-                        .iter()
-                        .map(|a| Person {
-                            name: a.clone(),
-                            email: None,
-                            uri: None,
-                        })
-                        .collect(),
+                    id: url.clone(),
+                    links: std::iter::once(Link {
+                        href: i.href.clone(),
+                        rel: "alternate".to_string(),
+                        ..Default::default()
+                    })
+                    .chain(i.page.as_ref().map(|page| Link {
+                        href: abs(page),
+                        rel: "related".to_string(),
+                        ..Default::default()
+                    }))
+                    .collect(),
+                    authors: i.authors.iter().map(|a| atom_person(config, a)).collect(),
+                    content: i.content.as_ref().map(|c| Content {
+                        value: Some(c.clone()),
+                        base: Some(url.clone()),
+                        content_type: Some("html".to_string()),
+                        ..Default::default()
+                    }),
+                    published: Some(pubts.into()),
                     updated: updts.into(),
                     ..Default::default()
                 }
             })
             .collect(),
-        updated: now.into(),
         ..Default::default()
     };
+    feed.updated = feed
+        .entries
+        .iter()
+        .map(|i| i.updated)
+        .max()
+        .unwrap_or_else(|| build_timestamp().into());
+
+    let f = OutFile::new(&outdir.join(fname), config.skip_unchanged);
+    let f = feed.write_to(f).expect("unable to serialize atom feed");
+    f.commit()?;
+
+    Ok(())
+}
+
+/// writes redirect stub pages (and optionally server redirect maps);
+/// `redirects` maps old paths to targets relative to the output directory, or URLs
+pub fn write_redirects(
+    config: &Config,
+    outdir: &Path,
+    redirects: &BTreeMap<Utf8PathBuf, String>,
+) -> std::io::Result<()> {
+    let site = site_path(config);
+
+    for (from, to) in redirects {
+        println!("- redirect: {} -> {}", from, to);
+        let href = if is_absolute_href(to) {
+            to.clone()
+        } else {
+            root_prefix(from.as_std_path()) + to
+        };
+        let href = escape_html(&href);
+        let fpath = outdir.join(from);
+        if let Some(x) = fpath.parent() {
+            std::fs::create_dir_all(x)?;
+        }
+        let mut f = OutFile::new(&fpath, config.skip_unchanged);
+        writeln!(
+            &mut f,
+            r#"<!doctype html>
+<html lang="{}" dir="ltr">
+  <head>
+    <meta charset="utf-8" />
+    <meta http-equiv="refresh" content="0; url={}" />
+    <link rel="canonical" href="{}" />
+    <meta name="robots" content="noindex" />
+    <title>Weiterleitung &mdash; {}</title>
+  </head>
+  <body>
+    Diese Seite wurde verschoben: <a href="{}">{}</a>
+  </body>
+</html>"#,
+            escape_html(&config.lang),
+            href,
+            escape_html(&absolute_url(config, Path::new(""), to)),
+            config.blog_name,
+            href,
+            href,
+        )?;
+        f.commit()?;
+    }
+
+    let server_target = |to: &str| {
+        if is_absolute_href(to) {
+            to.to_string()
+        } else {
+            format!("{}/{}", site, to)
+        }
+    };
+    if config.redirect_files.netlify {
+        let mut f = OutFile::new(&outdir.join("_redirects"), config.skip_unchanged);
+        for (from, to) in redirects {
+            writeln!(&mut f, "{}/{} {} 301", site, from, server_target(to))?;
+        }
+        f.commit()?;
+    }
+    if config.redirect_files.nginx {
+        // usage: map $uri $redirect_target { include redirects.map; }
+        let mut f = OutFile::new(&outdir.join("redirects.map"), config.skip_unchanged);
+        for (from, to) in redirects {
+            writeln!(&mut f, "{}/{} {};", site, from, server_target(to))?;
+        }
+        f.commit()?;
+    }
+    Ok(())
+}
+
+pub fn write_search_index(
+    config: &Config,
+    outdir: &Path,
+    ents: &[SearchEntry],
+) -> std::io::Result<()> {
+    println!("- search index");
+    let mut f = OutFile::new(&outdir.join("search-index.json"), config.skip_unchanged);
+    serde_json::to_writer(&mut f, ents)?;
+    f.commit()?;
+    Ok(())
+}
 
-    let fpath = outdir.join("feed.atom");
-    let f = std::io::BufWriter::new(std::fs::File::create(fpath)?);
-    let mut f = feed.write_to(f).expect("unable to serialize atom feed");
-    f.flush()?;
-    f.into_inner()?.sync_all()?;
+const SEARCH_SCRIPT: &str = r#"<script>
+(function () {
+  var idx = null;
+  var q = document.getElementById('q');
+  var res = document.getElementById('results');
+  function esc(s) {
+    return s.replace(/[&<>"]/g, function (c) {
+      return { '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;' }[c];
+    });
+  }
+  function run() {
+    var terms = q.value.toLowerCase().split(/\s+/).filter(Boolean);
+    res.innerHTML = '';
+    if (!idx || !terms.length) return;
+    idx.filter(function (e) {
+      var hay = (e.title + ' ' + e.tags.join(' ') + ' ' + e.body).toLowerCase();
+      return terms.every(function (t) { return hay.indexOf(t) !== -1; });
+    }).forEach(function (e) {
+      var li = document.createElement('li');
+      li.innerHTML = esc(e.date) + ': <a href="' + esc(e.href) + '">' + esc(e.title) + '</a>';
+      res.appendChild(li);
+    });
+  }
+  fetch('search-index.json')
+    .then(function (r) { return r.json(); })
+    .then(function (d) { idx = d; run(); });
+  q.addEventListener('input', run);
+})();
+</script>"#;
 
+pub fn write_search_page(config: &Config, outdir: &Path) -> std::io::Result<()> {
+    println!("- search page");
+    let mut f = OutFile::new(&outdir.join("search.html"), config.skip_unchanged);
+    writeln!(
+        &mut f,
+        r#"<!doctype html>
+<html lang="{}" dir="ltr">
+  <head>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+    <link rel="stylesheet" href="{}" type="text/css" />
+    <title>Suche &mdash; {}</title>
+{}  </head>
+  <body>
+    <h1>Suche &mdash; {}</h1>
+{}    <a href="index.html">[Hauptseite]</a><br />
+    <input type="search" id="q" placeholder="Suchbegriff" autofocus />
+    <ul id="results"></ul>
+{}
+  </body>
+</html>"#,
+        escape_html(&config.lang),
+        &config.stylesheet,
+        &config.blog_name,
+        &config.x_head,
+        &config.blog_name,
+        &config.x_body_ph1,
+        SEARCH_SCRIPT,
+    )?;
+    f.commit()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MangleConfig;
+
+    #[test]
+    fn content() {
+        let m = Mangler::new(&["div", "pre"], false);
+        let render = |mangle, content| {
+            let mut ret = Vec::new();
+            write_content(&m, &mut ret, mangle, "de", content).unwrap();
+            String::from_utf8(ret).unwrap()
+        };
+        assert_eq!(render(false, "<div>x</div>"), "<br />\n<div>x</div>\n");
+        assert_eq!(
+            render(true, "a\n\n<pre>b</pre>"),
+            "\n    <p>\n    a\n    </p>\n<pre>b</pre>\n"
+        );
+        assert_eq!(render(true, "<pre>b</pre>"), "<br />\n<pre>b</pre>\n");
+        // the typography pass is disabled by default
+        let input = "<div>\"a\" -- b...</div>";
+        assert_eq!(render(true, input), format!("<br />\n{}\n", input));
+        let m = Mangler::with_config(&MangleConfig {
+            typography: true,
+            ..Default::default()
+        });
+        let mut ret = Vec::new();
+        write_content(&m, &mut ret, true, "de", input).unwrap();
+        assert_eq!(
+            String::from_utf8(ret).unwrap(),
+            "<br />\n<div>&bdquo;a&ldquo; &ndash; b&hellip;</div>\n"
+        );
+    }
+
+    #[test]
+    fn breadcrumbs() {
+        let mut dirs = BTreeMap::new();
+        let meta = |title: &str| DirMeta {
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        dirs.insert(Utf8PathBuf::from("a"), meta("A"));
+        dirs.insert(Utf8PathBuf::from("a/b/c"), meta("C"));
+        let mut idxs: BTreeSet<_> = ["a", "a/b", "a/b/c"]
+            .iter()
+            .map(Utf8PathBuf::from)
+            .collect();
+
+        assert_eq!(
+            render_breadcrumbs(&dirs, &idxs, Utf8Path::new(""), false),
+            ""
+        );
+        assert_eq!(
+            render_breadcrumbs(&dirs, &idxs, Utf8Path::new("a/b/c"), false),
+            "<nav class=\"breadcrumbs\"><a href=\"../../../index.html\">Hauptseite</a> &rsaquo; <a href=\"../../../a/index.html\">A</a> &rsaquo; <a href=\"../../../a/b/index.html\">b</a> &rsaquo; <a href=\"../../../a/b/c/index.html\">C</a></nav>"
+        );
+        assert_eq!(
+            render_breadcrumbs(&dirs, &idxs, Utf8Path::new("a"), true),
+            "<nav class=\"breadcrumbs\"><a href=\"../index.html\">Hauptseite</a> &rsaquo; <span aria-current=\"page\">A</span></nav>"
+        );
+        // directories without an index (e.g. only containing pages) aren't linked
+        idxs.remove(Utf8Path::new("a/b/c"));
+        assert_eq!(
+            render_breadcrumbs(&dirs, &idxs, Utf8Path::new("a/b/c"), false),
+            "<nav class=\"breadcrumbs\"><a href=\"../../../index.html\">Hauptseite</a> &rsaquo; <a href=\"../../../a/index.html\">A</a> &rsaquo; <a href=\"../../../a/b/index.html\">b</a></nav>"
+        );
+        assert_eq!(
+            render_breadcrumbs(&dirs, &BTreeSet::new(), Utf8Path::new("a"), false),
+            ""
+        );
+    }
+}
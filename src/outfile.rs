@@ -0,0 +1,96 @@
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+
+/// output file which only appears at its destination once it is complete
+///
+/// The contents are buffered and written to a temporary file in the same
+/// directory on `commit`, which then gets renamed into place.
+pub struct OutFile {
+    dst: PathBuf,
+    buf: Vec<u8>,
+    skip_unchanged: bool,
+}
+
+impl OutFile {
+    /// if `skip_unchanged` is set, an existing destination file with identical
+    /// contents is left alone (which keeps its mtime)
+    pub fn new(dst: &Path, skip_unchanged: bool) -> Self {
+        Self {
+            dst: dst.to_path_buf(),
+            buf: Vec::new(),
+            skip_unchanged,
+        }
+    }
+
+    /// returns `false` if the destination was left unchanged
+    pub fn commit(self) -> Result<bool> {
+        if self.skip_unchanged {
+            if let Ok(old) = std::fs::read(&self.dst) {
+                if old == self.buf {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(self.dst.file_name().expect("output path without file name"));
+        tmp_name.push(format!(".{}.tmp", std::process::id()));
+        let tmp = self.dst.with_file_name(tmp_name);
+
+        let ret = (|| {
+            let mut f = std::fs::File::create(&tmp)?;
+            f.write_all(&self.buf)?;
+            f.sync_all()?;
+            std::fs::rename(&tmp, &self.dst)
+        })();
+        if ret.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        ret.map(|()| true)
+    }
+}
+
+impl Write for OutFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.buf.write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit() {
+        let dir = std::env::temp_dir().join(format!("zsstwebr-outfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dst = dir.join("x.html");
+
+        let mut f = OutFile::new(&dst, true);
+        write!(&mut f, "hello").unwrap();
+        // nothing is written before commit
+        assert!(!dst.exists());
+        assert!(f.commit().unwrap());
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "hello");
+
+        let mut f = OutFile::new(&dst, true);
+        write!(&mut f, "hello").unwrap();
+        assert!(!f.commit().unwrap());
+
+        let mut f = OutFile::new(&dst, false);
+        write!(&mut f, "hello").unwrap();
+        assert!(f.commit().unwrap());
+
+        // no temporary files are left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use crate::utils::{slugify, PostTyp};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize)]
+struct NewPost<'a> {
+    title: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    typ: PostTyp,
+}
+
+/// searches for a file name `{prefix}{slug}` which isn't used yet in `dir`,
+/// ignoring file extensions (which would collide in the output directory)
+fn free_file_name(dir: &Path, prefix: &str, slug: &str) -> String {
+    let taken: Vec<String> = std::fs::read_dir(dir)
+        .map(|rd| {
+            rd.filter_map(|i| i.ok())
+                .filter_map(|i| {
+                    Path::new(&i.file_name())
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .map(str::to_string)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut name = format!("{}{}", prefix, slug);
+    let mut n = 1;
+    while taken.contains(&name) {
+        n += 1;
+        name = format!("{}{}-{}", prefix, slug, n);
+    }
+    name
+}
+
+/// creates a new post in `indir`/`subdir` dated today, returns the path of the new file
+pub fn run(
+    indir: &Path,
+    subdir: Option<&str>,
+    title: &str,
+    tags: Vec<String>,
+    link: Option<&str>,
+) -> std::io::Result<PathBuf> {
+    let today = chrono::Local::now().date_naive();
+    // e.g. `2021/0101`
+    let diary = yz_diary_date::fmt(&today, None);
+    let (year, prefix) = diary.split_once('/').unwrap();
+
+    let mut dir = indir.to_path_buf();
+    if let Some(x) = subdir {
+        dir.push(x);
+    }
+    dir.push(year);
+    std::fs::create_dir_all(&dir)?;
+
+    let mut slug = slugify(title);
+    if slug.is_empty() {
+        slug = "post".to_string();
+    }
+    let fpath = dir.join(free_file_name(&dir, prefix, &slug));
+
+    let header = serde_yaml::to_string(&NewPost {
+        title,
+        tags: &tags,
+        typ: if link.is_some() {
+            PostTyp::Link
+        } else {
+            PostTyp::Text
+        },
+    })
+    .expect("unable to serialize front matter");
+    let data = format!("{}---\n{}\n", header, link.unwrap_or(""));
+
+    // don't overwrite existing files, even if they appeared just now
+    use std::io::Write;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&fpath)?
+        .write_all(data.as_bytes())?;
+    Ok(fpath)
+}
+
+/// opens `fpath` in `$VISUAL` or `$EDITOR`
+pub fn edit(fpath: &Path) -> std::io::Result<()> {
+    let editor = std::env::var_os("VISUAL")
+        .or_else(|| std::env::var_os("EDITOR"))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "neither $VISUAL nor $EDITOR is set",
+            )
+        })?;
+    // allow things like `EDITOR="code --wait"`
+    let editor = editor.to_string_lossy();
+    let mut parts = editor.split_whitespace();
+    let status = std::process::Command::new(parts.next().unwrap_or("vi"))
+        .args(parts)
+        .arg(fpath)
+        .status()?;
+    if !status.success() {
+        eprintln!("editor exited with {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Post;
+
+    fn tmpdir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("zsstwebr-scaffold-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn free_file_names() {
+        let dir = tmpdir("free");
+        assert_eq!(free_file_name(&dir, "0101", "hi"), "0101hi");
+        std::fs::write(dir.join("0101hi"), "").unwrap();
+        // extensions are ignored
+        std::fs::write(dir.join("0101hi-2.md"), "").unwrap();
+        assert_eq!(free_file_name(&dir, "0101", "hi"), "0101hi-3");
+        assert_eq!(free_file_name(&dir, "0102", "hi"), "0102hi");
+        // a missing directory doesn't contain any files
+        assert_eq!(free_file_name(&dir.join("x"), "0101", "hi"), "0101hi");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn front_matter() {
+        let dir = tmpdir("fm");
+        let read = |fpath: &Path| {
+            let data = std::fs::read_to_string(fpath).unwrap();
+            let (fm, content) = crate::fmatter::split(&data).unwrap();
+            (fm.parse::<Post>().unwrap(), content.to_string())
+        };
+
+        let tags = vec!["a".to_string(), "b c".to_string()];
+        let fpath = run(&dir, Some("fld"), "Hello: World", tags.clone(), None).unwrap();
+        let srcrel = fpath.strip_prefix(&dir).unwrap();
+        assert!(srcrel.starts_with("fld"));
+        assert!(fpath.to_str().unwrap().ends_with("hello-world"));
+        let cdate = yz_diary_date::parse_from_utf8path(
+            camino::Utf8Path::from_path(srcrel.strip_prefix("fld").unwrap()).unwrap(),
+        );
+        assert_eq!(cdate, Some(chrono::Local::now().date_naive()));
+        let (post, content) = read(&fpath);
+        assert_eq!(
+            (post.title.as_str(), post.tags, post.typ),
+            ("Hello: World", tags, PostTyp::Text)
+        );
+        assert_eq!(content, "\n");
+
+        // the same title gets a new file name
+        let fpath2 = run(
+            &dir,
+            Some("fld"),
+            "Hello: World",
+            Vec::new(),
+            Some("https://x/"),
+        )
+        .unwrap();
+        assert_ne!(fpath, fpath2);
+        let (post, content) = read(&fpath2);
+        assert_eq!(post.typ, PostTyp::Link);
+        assert!(post.tags.is_empty());
+        assert_eq!(post.link_target(&content), Some("https://x/"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use crate::utils::{escape_html, slugify, strip_html};
+use std::collections::HashSet;
+
+/// marker which gets replaced with the table of contents
+pub const TOC_MARKER: &str = "<!-- toc -->";
+
+pub struct Heading {
+    pub level: u8,
+    pub id: String,
+    pub text: String,
+}
+
+/// tries to parse the start of a `h2` .. `h6` opening tag,
+/// returns the level and the length of the opening tag
+fn parse_heading_open(s: &str) -> Option<(u8, usize)> {
+    let b = s.as_bytes();
+    if b.len() < 4 || b[0] != b'<' || !b[1].eq_ignore_ascii_case(&b'h') {
+        return None;
+    }
+    let level = match b[2] {
+        x @ b'2'..=b'6' => x - b'0',
+        _ => return None,
+    };
+    if !matches!(b[3], b'>' | b' ' | b'\t' | b'\r' | b'\n') {
+        return None;
+    }
+    // search the end of the tag, skipping quoted attribute values
+    let mut quote = None;
+    for (n, &c) in b.iter().enumerate().skip(3) {
+        match (quote, c) {
+            (None, b'"' | b'\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, b'>') => return Some((level, n + 1)),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// extracts the value of an existing `id` attribute
+fn get_id_attr(attrs: &str) -> Option<&str> {
+    let lower = attrs.to_ascii_lowercase();
+    let mut start = 0;
+    while let Some(pos) = lower[start..].find("id=") {
+        let pos = start + pos;
+        start = pos + 3;
+        if pos != 0 && !lower.as_bytes()[pos - 1].is_ascii_whitespace() {
+            continue;
+        }
+        let val = &attrs[pos + 3..];
+        return Some(match val.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let val = &val[1..];
+                &val[..val.find(q).unwrap_or(val.len())]
+            }
+            _ => {
+                let end = val
+                    .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+                    .unwrap_or(val.len());
+                &val[..end]
+            }
+        });
+    }
+    None
+}
+
+/// adds `id` anchors to all `h2` .. `h6` headings in `input`,
+/// existing ids are kept. Returns the modified content and all found headings.
+pub fn add_heading_anchors(input: &str, self_links: bool) -> (String, Vec<Heading>) {
+    let mut ret = String::with_capacity(input.len());
+    let mut headings = Vec::new();
+    // existing ids are collected first, so that generated ids don't collide with later ones
+    let explicit_ids: HashSet<&str> = input
+        .match_indices('<')
+        .filter_map(|(pos, _)| {
+            let (_, olen) = parse_heading_open(&input[pos..])?;
+            get_id_attr(&input[pos + 3..pos + olen])
+        })
+        .collect();
+    let mut used_ids = HashSet::new();
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('<') {
+        ret += &rest[..pos];
+        rest = &rest[pos..];
+        let (level, olen) = match parse_heading_open(rest) {
+            Some(x) => x,
+            None => {
+                ret.push('<');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let close = format!("</h{}", level);
+        let inner_len = match rest[olen..].to_ascii_lowercase().find(&close) {
+            Some(x) => x,
+            None => {
+                // unterminated heading, leave it alone
+                ret.push('<');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let otag = &rest[..olen];
+        let inner = &rest[olen..olen + inner_len];
+        let text = strip_html(inner);
+
+        let id = match get_id_attr(&otag[3..]) {
+            Some(id) => {
+                if used_ids.contains(id) {
+                    eprintln!("   - duplicate heading id: {}", id);
+                }
+                ret += otag;
+                id.to_string()
+            }
+            None => {
+                let mut base = slugify(&text);
+                if base.is_empty() {
+                    base = "section".to_string();
+                }
+                let mut id = base.clone();
+                let mut n = 1;
+                while used_ids.contains(&id) || explicit_ids.contains(id.as_str()) {
+                    n += 1;
+                    id = format!("{}-{}", base, n);
+                }
+                ret += &otag[..3];
+                ret += " id=\"";
+                ret += &id;
+                ret.push('"');
+                ret += &otag[3..];
+                id
+            }
+        };
+        ret += inner;
+        if self_links {
+            ret += &format!(
+                " <a class=\"heading-anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                escape_html(&id)
+            );
+        }
+        rest = &rest[olen + inner_len..];
+        used_ids.insert(id.clone());
+        headings.push(Heading { level, id, text });
+    }
+    ret += rest;
+    (ret, headings)
+}
+
+/// renders a nested list of links to the given headings
+pub fn render_toc(headings: &[Heading]) -> String {
+    let mut ret = String::from("<nav class=\"toc\">\n");
+    let mut stack: Vec<u8> = Vec::new();
+    for i in headings {
+        while let Some(&top) = stack.last() {
+            if i.level >= top {
+                break;
+            }
+            ret += "</li>\n</ul>\n";
+            stack.pop();
+        }
+        match stack.last() {
+            Some(&top) if top == i.level => ret += "</li>\n",
+            _ => {
+                ret += "<ul>\n";
+                stack.push(i.level);
+            }
+        }
+        ret += &format!(
+            "<li><a href=\"#{}\">{}</a>",
+            escape_html(&i.id),
+            escape_html(&i.text)
+        );
+    }
+    for _ in stack {
+        ret += "</li>\n</ul>\n";
+    }
+    ret += "</nav>";
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors() {
+        let (out, hs) = add_heading_anchors(
+            "<h2>Intro</h2>\n<H3 class=\"x\">Foo &amp; Bar</H3>\n<h2>Intro</h2>\n<h2 id=\"keep\">K</h2><h1>T</h1>",
+            false,
+        );
+        assert_eq!(
+            out,
+            "<h2 id=\"intro\">Intro</h2>\n<H3 id=\"foo-bar\" class=\"x\">Foo &amp; Bar</H3>\n<h2 id=\"intro-2\">Intro</h2>\n<h2 id=\"keep\">K</h2><h1>T</h1>"
+        );
+        let ids: Vec<_> = hs.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["intro", "foo-bar", "intro-2", "keep"]);
+        assert_eq!(hs[1].text, "Foo & Bar");
+    }
+
+    #[test]
+    fn anchors_explicit_later() {
+        let (out, _) = add_heading_anchors("<h2>Intro</h2><h2 id=\"intro\">X</h2>", false);
+        assert_eq!(out, "<h2 id=\"intro-2\">Intro</h2><h2 id=\"intro\">X</h2>");
+    }
+
+    #[test]
+    fn anchors_self_links() {
+        let (out, _) = add_heading_anchors("<h4>A</h4><header>x</header>", true);
+        assert_eq!(
+            out,
+            "<h4 id=\"a\">A <a class=\"heading-anchor\" href=\"#a\" aria-hidden=\"true\">#</a></h4><header>x</header>"
+        );
+    }
+
+    #[test]
+    fn toc_nesting() {
+        let (_, hs) = add_heading_anchors("<h2>A</h2><h3>B</h3><h3>C</h3><h2>D</h2>", false);
+        assert_eq!(
+            render_toc(&hs),
+            "<nav class=\"toc\">\n<ul>\n<li><a href=\"#a\">A</a><ul>\n<li><a href=\"#b\">B</a></li>\n<li><a href=\"#c\">C</a></li>\n</ul>\n</li>\n<li><a href=\"#d\">D</a></li>\n</ul>\n</nav>"
+        );
+    }
+}
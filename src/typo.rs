@@ -0,0 +1,359 @@
+/// elements whose content is never modified
+const SKIP_TAGS: &[&str] = &["code", "kbd", "pre", "samp", "script", "style", "tt"];
+
+/// elements which separate the surrounding text, like a line break or whitespace would
+pub const BREAK_TAGS: &[&str] = &[
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dt",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// units which are bound to the preceding number with a non-breaking space
+const UNITS: &[&str] = &[
+    "%", "€", "$", "°", "°C", "°F", "EUR", "Euro", "GB", "GHz", "GiB", "Hz", "KB", "KiB", "MB",
+    "MHz", "MiB", "Prozent", "TB", "TiB", "USD", "Uhr", "V", "W", "cm", "g", "h", "kB", "kHz",
+    "kW", "kg", "km", "km/h", "l", "m", "m/s", "mg", "min", "ml", "mm", "ms", "s",
+];
+
+struct Quotes {
+    dopen: &'static str,
+    dclose: &'static str,
+    sopen: &'static str,
+    sclose: &'static str,
+}
+
+/// quotation marks of the given language, straight quotes are kept for other languages
+fn quotes(lang: &str) -> Option<Quotes> {
+    Some(match lang {
+        "de" => Quotes {
+            dopen: "&bdquo;",
+            dclose: "&ldquo;",
+            sopen: "&sbquo;",
+            sclose: "&lsquo;",
+        },
+        "en" => Quotes {
+            dopen: "&ldquo;",
+            dclose: "&rdquo;",
+            sopen: "&lsquo;",
+            sclose: "&rsquo;",
+        },
+        _ => return None,
+    })
+}
+
+/// abbreviations (without the trailing dot) which are followed by a non-breaking space
+fn abbreviations(lang: &str) -> &'static [&'static str] {
+    match lang {
+        "de" => &[
+            "Abb", "Dr", "Fr", "Hr", "Nr", "Prof", "S", "ca", "d", "o", "s", "u", "vgl", "z",
+        ],
+        "en" => &[
+            "Dr", "Fig", "Mr", "Mrs", "Ms", "No", "Prof", "St", "ca", "p", "pp", "vs",
+        ],
+        _ => &[],
+    }
+}
+
+/// tries to parse a tag, comment or declaration at the start of `s`,
+/// returns its length, the lowercase tag name and if it is a closing tag
+fn parse_tag(s: &str) -> Option<(usize, String, bool)> {
+    if s.starts_with("<!--") {
+        let len = s.find("-->").map_or(s.len(), |x| x + 3);
+        return Some((len, String::new(), false));
+    }
+    let b = s.as_bytes();
+    let (closing, name_start) = match b.get(1)? {
+        b'/' => (true, 2),
+        b'!' | b'?' => (false, 1),
+        x if x.is_ascii_alphabetic() => (false, 1),
+        _ => return None,
+    };
+    let name: String = s[name_start..]
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    // search the end of the tag, skipping quoted attribute values;
+    // unterminated tags extend to the end of the input
+    let mut quote = None;
+    for (n, &c) in b.iter().enumerate().skip(name_start) {
+        match (quote, c) {
+            (None, b'"' | b'\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, b'>') => return Some((n + 1, name, closing)),
+            _ => {}
+        }
+    }
+    Some((s.len(), String::new(), false))
+}
+
+/// returns the length of the character reference at the start of `s`
+fn entity_len(s: &str) -> Option<usize> {
+    let end = s
+        .char_indices()
+        .skip(1)
+        .take(32)
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '#'))?;
+    if end.1 == ';' && end.0 > 1 {
+        Some(end.0 + 1)
+    } else {
+        None
+    }
+}
+
+/// applies typographic conventions of the given language to the text of
+/// (already mangled) HTML content: smart quotes, dashes, ellipses and
+/// non-breaking spaces before units and after abbreviations.
+/// Tags, attributes and the content of `pre`, `code` etc. are kept as-is.
+pub fn typeset(input: &str, lang: &str) -> String {
+    let lang = lang.split('-').next().unwrap_or(lang);
+    let quotes = quotes(lang);
+    let abbrs = abbreviations(lang);
+
+    let mut ret = String::with_capacity(input.len() + input.len() / 8);
+    // number of open elements whose content is skipped
+    let mut skip = 0usize;
+    // the last two characters of the text (without tags), newest first
+    let (mut prev, mut prev2) = (' ', ' ');
+    // the last output was an opening quote
+    let mut prev_open = false;
+    // an opening single quote wasn't closed yet
+    let mut single_open = false;
+    // the current run of alphanumeric characters, and the one preceding the last dot
+    let mut word = String::new();
+    let mut dot_word = String::new();
+    let mut rest = input;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some((len, name, closing)) = parse_tag(rest) {
+                // self-closing tags don't open an element
+                let self_closing = rest[..len].ends_with("/>");
+                ret += &rest[..len];
+                rest = &rest[len..];
+                if SKIP_TAGS.contains(&name.as_str()) && !self_closing {
+                    if closing {
+                        skip = skip.saturating_sub(1);
+                    } else {
+                        skip += 1;
+                    }
+                }
+                if BREAK_TAGS.contains(&name.as_str()) {
+                    (prev, prev2) = (' ', ' ');
+                    prev_open = false;
+                    word.clear();
+                }
+                continue;
+            }
+        }
+        let next = rest[c.len_utf8()..].chars().next();
+        let open_ctx = prev_open || prev.is_whitespace() || "([{-/–—".contains(prev);
+        let mut len = c.len_utf8();
+        let mut cur = c;
+        let mut is_open = false;
+        let elen = if c == '&' { entity_len(rest) } else { None };
+
+        if skip > 0 {
+            ret.push(c);
+        } else if let Some(elen) = elen {
+            len = elen;
+            cur = match &rest[..elen] {
+                "&nbsp;" | "&#160;" | "&#xa0;" | "&#xA0;" => '\u{a0}',
+                "&ndash;" => '–',
+                "&mdash;" => '—',
+                _ => ';',
+            };
+            ret += &rest[..elen];
+        } else {
+            match (c, &quotes) {
+                ('.', _) if rest.starts_with("...") => {
+                    ret += "&hellip;";
+                    len = 3;
+                    cur = '…';
+                }
+                ('.', _) => {
+                    dot_word = std::mem::take(&mut word);
+                    ret.push('.');
+                }
+                ('-', _) if rest.starts_with("---") => {
+                    ret += "&mdash;";
+                    len = 3;
+                    cur = '—';
+                }
+                // en dash, except for command line options like `--force`
+                ('-', _)
+                    if rest.starts_with("--")
+                        && !(prev.is_whitespace()
+                            && rest[2..].starts_with(char::is_alphanumeric)) =>
+                {
+                    ret += "&ndash;";
+                    len = 2;
+                    cur = '–';
+                }
+                ('-', _) if prev == ' ' && !prev2.is_whitespace() && next == Some(' ') => {
+                    ret += "&ndash;";
+                    cur = '–';
+                }
+                ('"', Some(q)) => {
+                    is_open = open_ctx;
+                    ret += if is_open { q.dopen } else { q.dclose };
+                }
+                ('\'', Some(q)) => {
+                    let next_alnum = next.is_some_and(char::is_alphanumeric);
+                    if prev.is_alphanumeric() && next_alnum {
+                        ret += "&rsquo;";
+                    } else if open_ctx {
+                        if next.is_some_and(|c| c.is_ascii_digit()) {
+                            // elided century, like '90
+                            ret += "&rsquo;";
+                        } else {
+                            is_open = true;
+                            single_open = true;
+                            ret += q.sopen;
+                        }
+                    } else if single_open {
+                        single_open = false;
+                        ret += q.sclose;
+                    } else {
+                        ret += "&rsquo;";
+                    }
+                }
+                (' ', _) if prev == '.' && abbrs.contains(&dot_word.as_str()) => {
+                    ret += "&nbsp;";
+                    cur = '\u{a0}';
+                }
+                (' ', _) if prev.is_ascii_digit() => {
+                    let unit: String = rest[1..]
+                        .chars()
+                        .take_while(|&c| c.is_alphanumeric() || "/°%€$".contains(c))
+                        .collect();
+                    if UNITS.contains(&unit.as_str()) {
+                        ret += "&nbsp;";
+                        cur = '\u{a0}';
+                    } else {
+                        ret.push(' ');
+                    }
+                }
+                _ => ret.push(c),
+            }
+        }
+
+        if cur.is_alphanumeric() {
+            word.push(cur);
+        } else {
+            word.clear();
+        }
+        prev2 = prev;
+        prev = cur;
+        prev_open = is_open;
+        rest = &rest[len..];
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_de() {
+        assert_eq!(
+            typeset("Er sagte: \"Das ist 'gut'\", oder? Geht's noch?", "de"),
+            "Er sagte: &bdquo;Das ist &sbquo;gut&lsquo;&ldquo;, oder? Geht&rsquo;s noch?"
+        );
+        assert_eq!(
+            typeset("(\"<em>a</em>\")", "de-AT"),
+            "(&bdquo;<em>a</em>&ldquo;)"
+        );
+    }
+
+    #[test]
+    fn quotes_en() {
+        assert_eq!(
+            typeset("\"Don't,\" she said, 'in the '90s'.", "en"),
+            "&ldquo;Don&rsquo;t,&rdquo; she said, &lsquo;in the &rsquo;90s&rsquo;."
+        );
+        // other languages keep straight quotes
+        assert_eq!(typeset("\"x\"", "fr"), "\"x\"");
+    }
+
+    #[test]
+    fn dashes_ellipses() {
+        assert_eq!(
+            typeset("1990--2000 - nun ja --- warte...", "de"),
+            "1990&ndash;2000 &ndash; nun ja &mdash; warte&hellip;"
+        );
+        // list-like line starts and words with hyphens are kept
+        assert_eq!(typeset("    - x-y", "de"), "    - x-y");
+        assert_eq!(
+            typeset("use --force -- or -x", "en"),
+            "use --force &ndash; or -x"
+        );
+        assert_eq!(typeset("a--b", "en"), "a&ndash;b");
+    }
+
+    #[test]
+    fn spaces() {
+        assert_eq!(
+            typeset("z. B. 5 km und 10 % bzw. 3 Äpfel, vgl. S. 4", "de"),
+            "z.&nbsp;B. 5&nbsp;km und 10&nbsp;% bzw. 3 Äpfel, vgl.&nbsp;S.&nbsp;4"
+        );
+        assert_eq!(
+            typeset("Mr. Smith, 5 kg.", "en"),
+            "Mr.&nbsp;Smith, 5&nbsp;kg."
+        );
+    }
+
+    #[test]
+    fn skipped() {
+        let input = "<a href=\"a--b\" title='\"x\"'>\"y\"</a> <code>\"c\" -- d</code> <pre>\n'p'...\n</pre><!-- \"--\" --> &amp; \"e\"";
+        assert_eq!(
+            typeset(input, "en"),
+            "<a href=\"a--b\" title='\"x\"'>&ldquo;y&rdquo;</a> <code>\"c\" -- d</code> <pre>\n'p'...\n</pre><!-- \"--\" --> &amp; &ldquo;e&rdquo;"
+        );
+        assert_eq!(
+            typeset("a < b <br />\"x\"", "en"),
+            "a < b <br />&ldquo;x&rdquo;"
+        );
+        // self-closing skipped elements don't start skipping
+        assert_eq!(typeset("<code/>\"x\"", "en"), "<code/>&ldquo;x&rdquo;");
+    }
+
+    #[test]
+    fn block_tags() {
+        // line breaks and block boundaries start a new context, like whitespace
+        assert_eq!(
+            typeset("Zeile<br />\"Zitat\"", "de"),
+            "Zeile<br />&bdquo;Zitat&ldquo;"
+        );
+        assert_eq!(
+            typeset("<p>a</p>\n<p>'b'</p><li>\"c\"</li>", "en"),
+            "<p>a</p>\n<p>&lsquo;b&rsquo;</p><li>&ldquo;c&rdquo;</li>"
+        );
+        assert_eq!(typeset("x<br>- y", "de"), "x<br>- y");
+        // inline elements don't
+        assert_eq!(
+            typeset("<em>\"a\"</em>\"", "en"),
+            "<em>&ldquo;a&rdquo;</em>&rdquo;"
+        );
+    }
+}
@@ -1,8 +1,14 @@
-use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use atom_syndication::TextType;
+use camino::{Utf8Path, Utf8PathBuf};
 use chrono::{naive::NaiveDate, DateTime, Utc};
-use serde::Deserialize;
-use std::{path::Path, time::SystemTime};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    time::SystemTime,
+};
 use walkdir::DirEntry;
 
 #[derive(Clone, Debug, Deserialize)]
@@ -11,30 +17,207 @@ pub struct Config {
     pub id: String,
     pub author: String,
     pub stylesheet: String,
+    // language of posts without `lang`, these are listed on the main index
+    #[serde(default = "default_lang")]
+    pub lang: String,
     #[serde(default)]
     pub x_head: String,
     #[serde(default)]
     pub x_nav: String,
     #[serde(default)]
     pub x_body_ph1: String,
+    #[serde(default)]
+    pub mangler: MangleConfig,
+    // add `#` self-links to headings in posts (ids are always added)
+    #[serde(default)]
+    pub heading_self_links: bool,
+
+    // site-wide defaults for posts without `description` or `image`
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub image: String,
+    // twitter handle of the site, e.g. `@example`
+    #[serde(default)]
+    pub twitter_site: String,
+    #[serde(default)]
+    pub search: SearchConfig,
+    // don't rewrite output files whose contents didn't change (keeps their mtime)
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    // output path pattern for posts, e.g. `{year}/{month}/{slug}.html`;
+    // available placeholders: year, month, day, dir (source directory), slug
+    #[serde(default)]
+    pub permalink: Option<String>,
+    // old path (relative to the output directory) -> new path or URL
+    #[serde(default)]
+    pub redirects: BTreeMap<String, String>,
+    #[serde(default)]
+    pub redirect_files: RedirectFiles,
+    // author profiles, referenced by id from posts
+    #[serde(default)]
+    pub authors: BTreeMap<String, AuthorProfile>,
+    // sort order of directory indexes, by output directory (`""` is the main index)
+    #[serde(default)]
+    pub index_order: BTreeMap<String, SortOrder>,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+/// name of the per-directory metadata file
+pub const DIR_META_FILE: &str = "_dir.yaml";
+
+// metadata of an input directory, read from `_dir.yaml`
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DirMeta {
+    // used instead of the path on the directory index and in links to it
+    #[serde(default)]
+    pub title: Option<String>,
+    // HTML, shown on the directory index
+    #[serde(default)]
+    pub description: String,
+    // overrides `Config::index_order`
+    #[serde(default)]
+    pub sort: Option<SortOrder>,
+
+    // overrides of the corresponding config fields, inherited by subdirectories
+    #[serde(default)]
+    pub stylesheet: Option<String>,
+    #[serde(default)]
+    pub x_head: Option<String>,
+    #[serde(default)]
+    pub x_nav: Option<String>,
+    #[serde(default)]
+    pub x_body_ph1: Option<String>,
+
+    #[serde(skip)]
+    pub mtime: Option<SystemTime>,
+}
+
+impl DirMeta {
+    /// applies the config overrides
+    pub fn apply(&self, config: &mut Config) {
+        let fields = [
+            (&self.stylesheet, &mut config.stylesheet),
+            (&self.x_head, &mut config.x_head),
+            (&self.x_nav, &mut config.x_nav),
+            (&self.x_body_ph1, &mut config.x_body_ph1),
+        ];
+        for (src, dst) in fields {
+            if let Some(x) = src {
+                dst.clone_from(x);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    DateDesc,
+    DateAsc,
+    Title,
+}
+
+// server configuration files for redirects, in addition to the HTML stubs
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RedirectFiles {
+    // `_redirects`, as used by Netlify, Cloudflare Pages etc.
+    #[serde(default)]
+    pub netlify: bool,
+    // `redirects.map`, usable in a nginx `map` block
+    #[serde(default)]
+    pub nginx: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AuthorProfile {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub uri: Option<String>,
+    // HTML, shown on the author index page
+    #[serde(default)]
+    pub bio: String,
+}
+
+impl Config {
+    /// renders a list of authors, linking to the author index pages;
+    /// `root` is the relative path to the root of the output directory
+    pub fn authors_html(&self, ids: &[String], root: &str) -> String {
+        let mut ret = String::new();
+        for (n, i) in ids.iter().enumerate() {
+            if n != 0 {
+                ret += ", ";
+            }
+            match self.authors.get(i) {
+                Some(prof) => {
+                    ret += &format!(
+                        "<a class=\"p-author h-card\" href=\"{}authors/{}.html\">{}</a>",
+                        root, i, prof.name
+                    )
+                }
+                None => ret += &format!("<span class=\"p-author h-card\">{}</span>", i),
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SearchConfig {
+    // generate `search-index.json`
+    #[serde(default)]
+    pub enabled: bool,
+    // maximum length (in characters) of the body of each entry
+    #[serde(default)]
+    pub max_body_len: Option<usize>,
+    // generate `search.html`, which queries the search index
+    #[serde(default)]
+    pub page: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct MangleConfig {
+    // replaces the default list of block-level tags, if set
+    #[serde(default)]
+    pub block_tags: Option<Vec<String>>,
+    // appended to the list of block-level tags
+    #[serde(default)]
+    pub extra_block_tags: Vec<String>,
+    // emit `<br />` for single newlines inside paragraphs
+    #[serde(default)]
+    pub line_breaks: bool,
+    // smart quotes, dashes etc. according to the language of the post (see `crate::typo`)
+    #[serde(default)]
+    pub typography: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum PostTyp {
     Link,
     Text,
+    // standalone page without date, not listed in indexes or feeds
+    Page,
+    // short post without a title of its own
+    Note,
+    // quotation, see `Post::source` and `Post::cite`
+    Quote,
+    // single image, see `Post::src`, `Post::alt` and `Post::caption`
+    Image,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Post {
+    // optional for notes, quotes and images (see `Post::fallback_title`)
+    #[serde(default)]
     pub title: String,
 
-    // used for index page
+    // author ids (see `Config::authors`) or free-text names;
+    // `author` is a shorthand for a single author
     #[serde(default)]
     pub author: String,
-
-    // used for Atom feed
     #[serde(default)]
     pub authors: Vec<String>,
     #[serde(default)]
@@ -44,12 +227,231 @@ pub struct Post {
     #[serde(default)]
     pub x_nav: String,
     pub typ: PostTyp,
+
+    // set to false for hand-written HTML
+    #[serde(default = "default_true")]
+    pub mangle: bool,
+
+    // generate a table of contents
+    #[serde(default)]
+    pub toc: bool,
+
+    // used for link previews
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub image: String,
+
+    // don't list the post in indexes, feeds or the search index
+    #[serde(default)]
+    pub unlisted: bool,
+    // list the post above all others in indexes
+    #[serde(default)]
+    pub pinned: bool,
+
+    // overrides the output file name (without extension)
+    #[serde(default)]
+    pub slug: Option<String>,
+    // old output paths, which should redirect to this post
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    // date of the last significant change (the diary date is the publishing date)
+    #[serde(default, deserialize_with = "de_opt_date")]
+    pub updated: Option<NaiveDate>,
+    // dated change notes, shown on the article page
+    #[serde(default)]
+    pub changes: Vec<ChangeNote>,
+
+    // link posts: the link target (otherwise the content is the link target),
+    // the content is then used as commentary; `via` is the URL where the link was found
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub via: String,
+
+    // language code, defaults to `Config::lang`
+    #[serde(default)]
+    pub lang: Option<String>,
+    // source path (relative to the input directory) of the post this one is a translation of
+    #[serde(default)]
+    pub translation_of: Option<String>,
+
+    // series this post is part of
+    #[serde(default)]
+    pub series: Option<SeriesRef>,
+
+    // quotes: who or what is quoted (HTML), and the URL of the source
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub cite: String,
+
+    // images: the image URL (relative to the post), its alt text and caption (HTML)
+    #[serde(default)]
+    pub src: String,
+    #[serde(default)]
+    pub alt: String,
+    #[serde(default)]
+    pub caption: String,
+}
+
+// either just the name of the series, or the name and the part number
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SeriesRef {
+    Name(String),
+    Part {
+        name: String,
+        #[serde(default)]
+        part: Option<u32>,
+    },
+}
+
+impl SeriesRef {
+    pub fn name(&self) -> &str {
+        match self {
+            SeriesRef::Name(name) | SeriesRef::Part { name, .. } => name,
+        }
+    }
+
+    pub fn part(&self) -> Option<u32> {
+        match self {
+            SeriesRef::Name(_) => None,
+            SeriesRef::Part { part, .. } => *part,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChangeNote {
+    #[serde(deserialize_with = "de_date")]
+    pub date: NaiveDate,
+    pub note: String,
+}
+
+impl Post {
+    /// returns the date of the last update, if any
+    pub fn last_updated(&self) -> Option<NaiveDate> {
+        self.changes
+            .iter()
+            .map(|i| i.date)
+            .chain(self.updated)
+            .max()
+    }
+
+    /// returns the link target of link posts
+    pub fn link_target<'a>(&'a self, content: &'a str) -> Option<&'a str> {
+        match self.typ {
+            PostTyp::Link if self.url.is_empty() => Some(content.trim()),
+            PostTyp::Link => Some(self.url.trim()),
+            _ => None,
+        }
+    }
+
+    /// whether an article page gets generated for the post
+    pub fn has_page(&self, content: &str) -> bool {
+        // link posts only get one if they contain commentary
+        self.typ != PostTyp::Link || (!self.url.is_empty() && !content.trim().is_empty())
+    }
+
+    /// whether the post gets a title derived from its content if none is given
+    pub fn is_untitled_typ(&self) -> bool {
+        matches!(self.typ, PostTyp::Note | PostTyp::Quote | PostTyp::Image)
+    }
+
+    /// title for untitled posts: an excerpt of the content (or the alt text of images)
+    pub fn fallback_title(&self, content: &str) -> String {
+        const MAX_LEN: usize = 60;
+        let text = match self.typ {
+            PostTyp::Image if !self.alt.is_empty() => self.alt.clone(),
+            PostTyp::Image if !self.caption.is_empty() => strip_html(&self.caption),
+            _ => strip_html(content),
+        };
+        let text = if text.is_empty() && !self.source.is_empty() {
+            strip_html(&self.source)
+        } else {
+            text
+        };
+        let ret = match text.char_indices().nth(MAX_LEN) {
+            None => text,
+            Some((pos, _)) => {
+                // cut at the last word boundary
+                let cut = text[..pos].rfind(' ').unwrap_or(pos);
+                format!("{}\u{2026}", text[..cut].trim_end())
+            }
+        };
+        escape_html(&ret)
+    }
+
+    pub fn lang<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.lang.as_deref().unwrap_or(&config.lang)
+    }
+
+    pub fn author_ids(&self) -> Vec<String> {
+        if !self.authors.is_empty() {
+            self.authors.clone()
+        } else if !self.author.is_empty() {
+            vec![self.author.clone()]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// dates are strings in YAML and JSON, but TOML has native dates
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DateRepr {
+    Str(String),
+    Toml(toml::value::Datetime),
+}
+
+fn de_date<'de, D: serde::Deserializer<'de>>(de: D) -> Result<NaiveDate, D::Error> {
+    use serde::de::Error;
+    match DateRepr::deserialize(de)? {
+        DateRepr::Str(x) => x.parse().map_err(D::Error::custom),
+        DateRepr::Toml(x) => x
+            .date
+            .filter(|_| x.time.is_none())
+            .and_then(|d| NaiveDate::from_ymd_opt(d.year.into(), d.month.into(), d.day.into()))
+            .ok_or_else(|| D::Error::custom(format!("expected a date, got {}", x))),
+    }
+}
+
+fn de_opt_date<'de, D: serde::Deserializer<'de>>(de: D) -> Result<Option<NaiveDate>, D::Error> {
+    de_date(de).map(Some)
+}
+
+fn default_lang() -> String {
+    "de".to_string()
+}
+
+/// returns the native name of a language, or the code for unknown languages
+pub fn lang_name(code: &str) -> &str {
+    match code {
+        "de" => "Deutsch",
+        "en" => "English",
+        "es" => "Espa&ntilde;ol",
+        "fr" => "Fran&ccedil;ais",
+        "it" => "Italiano",
+        "nl" => "Nederlands",
+        _ => code,
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum IndexTyp {
     Directory,
     Tag,
+    Author,
+    Series,
+    // main index of posts in another language
+    Language,
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -57,10 +459,16 @@ pub struct IndexEntry {
     pub cdate: NaiveDate,
     pub href: String,
     pub title: String,
-    // used for index page
-    pub author: String,
-    // used for Atom feed
     pub authors: Vec<String>,
+    pub updated: Option<NaiveDate>,
+    pub typ: PostTyp,
+    // HTML, included in the feed entry (used for untitled posts and link commentary)
+    pub content: Option<String>,
+    // href of the commentary page of link posts
+    pub page: Option<String>,
+    // URL where a linked page was found (link posts only)
+    pub via: String,
+    pub pinned: bool,
 }
 
 impl IndexEntry {
@@ -69,8 +477,70 @@ impl IndexEntry {
             cdate,
             href: lnk.to_string(),
             title: post.title.clone(),
-            author: post.author.clone(),
-            authors: post.authors.clone(),
+            authors: post.author_ids(),
+            updated: post.last_updated(),
+            typ: post.typ,
+            content: None,
+            page: None,
+            via: if post.typ == PostTyp::Link {
+                post.via.trim().to_string()
+            } else {
+                String::new()
+            },
+            pinned: post.pinned,
+        }
+    }
+}
+
+pub struct Series {
+    pub name: String,
+    // part number (if given) and entry of each post
+    pub parts: Vec<(Option<u32>, IndexEntry)>,
+}
+
+impl Series {
+    /// sorts the parts into reading order: numbered parts first, the rest by date
+    pub fn prepare(&mut self) {
+        self.parts
+            .sort_unstable_by(|a, b| (a.0.is_none(), a.0, &a.1).cmp(&(b.0.is_none(), b.0, &b.1)));
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct SearchEntry {
+    pub title: String,
+    pub href: String,
+    pub date: NaiveDate,
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+impl SearchEntry {
+    pub fn new(
+        config: &SearchConfig,
+        mangler: &Mangler,
+        post: &Post,
+        cdate: NaiveDate,
+        href: &str,
+        content: &str,
+    ) -> Self {
+        let mut body = if post.mangle {
+            let mangled: Vec<_> = mangler.mangle_content(content).map(|(_, i)| i).collect();
+            strip_html(&mangled.join("\n"))
+        } else {
+            strip_html(content)
+        };
+        if let Some(max_len) = config.max_body_len {
+            if let Some((pos, _)) = body.char_indices().nth(max_len) {
+                body.truncate(pos);
+            }
+        }
+        Self {
+            title: strip_html(&post.title),
+            href: href.to_string(),
+            date: cdate,
+            tags: post.tags.clone(),
+            body,
         }
     }
 }
@@ -79,10 +549,17 @@ impl IndexEntry {
 pub struct IndexRef {
     pub name: String,
     pub typ: IndexTyp,
+    // file name of the referenced index (without extension)
+    pub fname: String,
 }
 
 pub struct Index {
     pub typ: IndexTyp,
+    pub order: SortOrder,
+    // HTML, shown below the heading
+    pub description: String,
+    // HTML, replaces the link to the parent directory
+    pub breadcrumbs: String,
     pub oidxrefs: Vec<IndexRef>,
     pub ents: Vec<IndexEntry>,
 }
@@ -91,6 +568,9 @@ impl Default for Index {
     fn default() -> Self {
         Self {
             typ: IndexTyp::Directory,
+            order: SortOrder::DateDesc,
+            description: String::new(),
+            breadcrumbs: String::new(),
             oidxrefs: Vec::new(),
             ents: Vec::new(),
         }
@@ -98,23 +578,265 @@ impl Default for Index {
 }
 
 impl Index {
+    /// sorts the entries into display order, pinned entries first
     pub fn prepare(&mut self) {
         self.oidxrefs.sort_unstable();
-        self.ents.sort_unstable();
+        match self.order {
+            SortOrder::DateDesc => self.ents.sort_unstable_by(|a, b| b.cmp(a)),
+            SortOrder::DateAsc => self.ents.sort_unstable(),
+            SortOrder::Title => self.ents.sort_by_cached_key(|i| {
+                (strip_html(&i.title).to_lowercase(), i.cdate, i.href.clone())
+            }),
+        }
+        // stable, keeps the order within pinned and unpinned entries
+        self.ents.sort_by_key(|i| !i.pinned);
+    }
+}
+
+/// the indexes which are generated in addition to the posts, by their slugs;
+/// only listed posts contribute to them
+#[derive(Default)]
+pub struct SiteIndexes {
+    // output directories of posts, their ancestors get an index, too
+    pub dirs: BTreeSet<Utf8PathBuf>,
+    pub langs: BTreeSet<String>,
+    pub tags: BTreeSet<String>,
+    pub authors: BTreeSet<String>,
+    pub series: BTreeSet<String>,
+}
+
+impl SiteIndexes {
+    /// returns the paths of all generated files (relative to the output directory),
+    /// together with a description of their source;
+    /// used by the build and `check` to detect collisions with posts
+    pub fn outputs(&self, config: &Config) -> Vec<(Utf8PathBuf, String)> {
+        let mut ret = vec![
+            ("index.html".into(), "(main index)".to_string()),
+            ("feed.atom".into(), "(main index)".to_string()),
+        ];
+        if config.search.enabled {
+            ret.push(("search-index.json".into(), "(search)".to_string()));
+            if config.search.page {
+                ret.push(("search.html".into(), "(search)".to_string()));
+            }
+        }
+        for i in &self.langs {
+            let src = format!("(language {})", i);
+            ret.push((format!("index.{}.html", i).into(), src.clone()));
+            ret.push((format!("feed.{}.atom", i).into(), src));
+        }
+        for i in &self.tags {
+            ret.push((format!("{}.html", i).into(), format!("(tag {})", i)));
+        }
+        for i in &self.authors {
+            ret.push((
+                format!("authors/{}.html", i).into(),
+                format!("(author {})", i),
+            ));
+        }
+        for i in &self.series {
+            ret.push((
+                format!("series/{}.html", i).into(),
+                format!("(series {})", i),
+            ));
+        }
+        let dirs: BTreeSet<&Utf8Path> = self
+            .dirs
+            .iter()
+            .flat_map(|i| i.ancestors())
+            .filter(|i| !i.as_str().is_empty())
+            .collect();
+        for i in dirs {
+            ret.push((i.join("index.html"), "(directory index)".to_string()));
+        }
+        ret
+    }
+}
+
+pub fn load_config(path: &Path) -> std::io::Result<(Config, Option<SystemTime>)> {
+    let mut fh = std::fs::File::open(path)?;
+    let config_mtime = fh.metadata()?.modified().ok();
+    let fh_data = readfilez::read_part_from_file(
+        &mut fh,
+        0,
+        readfilez::LengthSpec {
+            bound: None,
+            is_exact: true,
+        },
+    )?;
+    let config = serde_yaml::from_slice(&fh_data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok((config, config_mtime))
+}
+
+pub fn load_dir_meta(path: &Path) -> std::io::Result<DirMeta> {
+    let fh_data = std::fs::read(path)?;
+    let mut meta: DirMeta = serde_yaml::from_slice(&fh_data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    meta.mtime = std::fs::metadata(path)?.modified().ok();
+    Ok(meta)
+}
+
+/// returns the title of the directory `dir` (relative to the input directory)
+pub fn dir_title(metas: &BTreeMap<Utf8PathBuf, DirMeta>, dir: &Utf8Path) -> String {
+    match metas.get(dir).and_then(|m| m.title.as_ref()) {
+        Some(title) => title.clone(),
+        None => dir.file_name().unwrap_or_default().to_string(),
+    }
+}
+
+/// returns the config for the directory `dir` (relative to the input directory),
+/// with the overrides of all its ancestors applied (except the root, which is applied to `config`),
+/// and the newest modification time of the used metadata files
+pub fn dir_config<'a>(
+    config: &'a Config,
+    metas: &BTreeMap<Utf8PathBuf, DirMeta>,
+    dir: &Utf8Path,
+) -> (Cow<'a, Config>, Option<SystemTime>) {
+    let mut ancestors: Vec<_> = dir
+        .ancestors()
+        .filter(|i| !i.as_str().is_empty())
+        .filter_map(|i| metas.get(i))
+        .collect();
+    if ancestors.is_empty() {
+        return (Cow::Borrowed(config), None);
+    }
+    ancestors.reverse();
+    let mut ret = config.clone();
+    for i in &ancestors {
+        i.apply(&mut ret);
+    }
+    let mtime = ancestors.iter().filter_map(|i| i.mtime).max();
+    (Cow::Owned(ret), mtime)
+}
+
+/// calculates the path of the output file of a post, relative to the output directory
+///
+/// `srcrel` is the path of the source file relative to the input directory.
+/// The file name can be overridden by `post.slug`, the whole path by `config.permalink`,
+/// which isn't applied to undated pages (`cdate` is `None`).
+pub fn output_rel_path(
+    config: &Config,
+    srcrel: &Utf8Path,
+    post: &Post,
+    cdate: Option<NaiveDate>,
+) -> Result<Utf8PathBuf, String> {
+    let slug = match &post.slug {
+        Some(x) if !is_valid_tag(x) => return Err(format!("invalid slug: {}", x)),
+        Some(x) => x.as_str(),
+        None => srcrel.file_stem().ok_or("source path without file name")?,
+    };
+    let ret = match (&config.permalink, cdate) {
+        (Some(pat), Some(cdate)) => {
+            let dir = srcrel.parent().map(Utf8Path::as_str).unwrap_or("");
+            let ret = pat
+                .replace("{year}", &cdate.format("%Y").to_string())
+                .replace("{month}", &cdate.format("%m").to_string())
+                .replace("{day}", &cdate.format("%d").to_string())
+                .replace("{dir}", dir)
+                .replace("{slug}", slug);
+            // collapse empty path components, e.g. from an empty `{dir}`
+            let ret: Vec<_> = ret.split('/').filter(|i| !i.is_empty()).collect();
+            Utf8PathBuf::from(ret.join("/"))
+        }
+        _ => match &post.slug {
+            Some(_) => srcrel.with_file_name(format!("{}.html", slug)),
+            // only the last extension is replaced, like `0101v1.2.md` -> `0101v1.2.html`
+            None => srcrel.with_extension("html"),
+        },
+    };
+    if ret.file_name().is_none()
+        || !ret
+            .components()
+            .all(|i| matches!(i, camino::Utf8Component::Normal(_)))
+    {
+        return Err(format!("invalid output path: {}", ret));
+    }
+    Ok(ret)
+}
+
+/// normalizes a redirect source path (relative to the output directory)
+pub fn normalize_alias(alias: &str) -> Result<Utf8PathBuf, String> {
+    let mut ret = Utf8PathBuf::from(alias.trim_start_matches('/'));
+    if alias.ends_with('/') || ret.as_str().is_empty() {
+        ret.push("index.html");
+    }
+    if ret
+        .components()
+        .all(|i| matches!(i, camino::Utf8Component::Normal(_)))
+    {
+        Ok(ret)
+    } else {
+        Err(format!("invalid alias: {}", alias))
+    }
+}
+
+/// returns the path component of `config.id`, without trailing slash;
+/// ids which aren't URLs (like `tag:` or `urn:` ids) don't have one
+pub fn site_path(config: &Config) -> &str {
+    let id = config.id.trim_end_matches('/');
+    match id.find("://") {
+        Some(i) => id[i + 3..]
+            .find('/')
+            .map(|j| &id[i + 3 + j..])
+            .unwrap_or(""),
+        None if id.starts_with('/') => id,
+        None => "",
     }
 }
 
+/// extracts the host name (without `www.`) of an absolute URL
+pub fn url_domain(url: &str) -> Option<&str> {
+    let rest = &url[url.find("://")? + 3..];
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    // strip user info and port
+    let host = host.rsplit('@').next().unwrap_or(host);
+    let host = host.split(':').next().unwrap_or(host);
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// relative path from the file `p` to the root of the output directory
+pub fn root_prefix(p: &Path) -> String {
+    "../".repeat(p.components().count().saturating_sub(1))
+}
+
 pub fn back_to_idx(p: &Path) -> String {
-    use std::iter::{once, repeat};
-    repeat("../")
-        .take(p.components().count() - 1)
-        .chain(once("index.html"))
-        .flat_map(|i| i.chars())
-        .collect()
+    root_prefix(p) + "index.html"
+}
+
+/// checks if `href` doesn't need a prefix when used on another page,
+/// i.e. it is a full URL or a server-absolute path
+pub fn is_absolute_href(href: &str) -> bool {
+    href.starts_with('/') || href.contains("://")
+}
+
+/// makes `href` (relative to the page at `fpath`) absolute, using `config.id` as base
+pub fn absolute_url(config: &Config, fpath: &Path, href: &str) -> String {
+    let base = config.id.trim_end_matches('/');
+    if href.contains("://") {
+        href.to_string()
+    } else if let Some(x) = href.strip_prefix('/') {
+        // server-absolute path, only keep the origin of the base
+        let origin_end = base
+            .find("://")
+            .and_then(|i| base[i + 3..].find('/').map(|j| i + 3 + j))
+            .unwrap_or(base.len());
+        format!("{}/{}", &base[..origin_end], x)
+    } else {
+        match fpath.parent().and_then(Path::to_str) {
+            Some(par) if !par.is_empty() => format!("{}/{}/{}", base, par, href),
+            _ => format!("{}/{}", base, href),
+        }
+    }
 }
 
 pub fn guess_text_type(text: &str) -> TextType {
-    if text.contains(|i| matches!(i, '<' | '>' | '&')) {
+    if text.contains(['<', '>', '&']) {
         TextType::Html
     } else {
         TextType::Text
@@ -122,7 +844,186 @@ pub fn guess_text_type(text: &str) -> TextType {
 }
 
 pub fn is_valid_tag(tag: &str) -> bool {
-    !(tag.is_empty() || tag.contains(|i| matches!(i, '.' | '/' | '\0')))
+    !(tag.is_empty() || tag.contains(['.', '/', '\0']))
+}
+
+/// normalizes a tag (or any other name) into something usable as file name and URL part
+pub fn slugify(s: &str) -> String {
+    use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+    let mut ret = String::with_capacity(s.len());
+    let mut pending_hyphen = false;
+    for i in s.nfkc().flat_map(char::to_lowercase) {
+        let tl: &str = match i {
+            'ä' => "ae",
+            'ö' => "oe",
+            'ü' => "ue",
+            'ß' => "ss",
+            'æ' => "ae",
+            'ø' => "oe",
+            'œ' => "oe",
+            'å' => "aa",
+            'þ' => "th",
+            'ð' => "d",
+            'ł' => "l",
+            '+' => "plus",
+            _ => "",
+        };
+        let mut push = |c: char| {
+            if pending_hyphen && !ret.is_empty() {
+                ret.push('-');
+            }
+            pending_hyphen = false;
+            ret.push(c);
+        };
+        if !tl.is_empty() {
+            tl.chars().for_each(&mut push);
+        } else if i.is_ascii_alphanumeric() {
+            push(i);
+        } else if i.is_alphanumeric() {
+            // strip diacritics, keep the base characters if they are ASCII
+            let mut any = false;
+            for j in core::iter::once(i)
+                .nfkd()
+                .filter(|&j| !is_combining_mark(j))
+            {
+                if j.is_ascii_alphanumeric() {
+                    push(j);
+                    any = true;
+                }
+            }
+            if !any {
+                // no transliteration available, keep the character as-is
+                push(i);
+            }
+        } else {
+            pending_hyphen = true;
+        }
+    }
+    ret
+}
+
+pub fn escape_html(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for i in s.chars() {
+        match i {
+            '&' => ret += "&amp;",
+            '<' => ret += "&lt;",
+            '>' => ret += "&gt;",
+            '"' => ret += "&quot;",
+            _ => ret.push(i),
+        }
+    }
+    ret
+}
+
+fn decode_entity(ent: &str) -> Option<char> {
+    Some(match ent {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "shy" => '\u{ad}',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "bdquo" => '„',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "sbquo" => '‚',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "laquo" => '«',
+        "raquo" => '»',
+        "auml" => 'ä',
+        "ouml" => 'ö',
+        "uuml" => 'ü',
+        "Auml" => 'Ä',
+        "Ouml" => 'Ö',
+        "Uuml" => 'Ü',
+        "szlig" => 'ß',
+        _ => {
+            let num = ent.strip_prefix('#')?;
+            let num = match num.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => num.parse(),
+            };
+            return num.ok().and_then(char::from_u32);
+        }
+    })
+}
+
+/// converts a HTML fragment into plain text
+/// (strips tags and comments, decodes entities and collapses whitespace)
+pub fn strip_html(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut rest = s;
+    let mut pending_space = false;
+    let mut push = |ret: &mut String, c: char| {
+        if c.is_whitespace() {
+            pending_space = true;
+            return;
+        }
+        if pending_space && !ret.is_empty() {
+            ret.push(' ');
+        }
+        pending_space = false;
+        ret.push(c);
+    };
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' => {
+                if let Some(x) = rest.strip_prefix("<!--") {
+                    rest = x.find("-->").map(|e| &x[e + 3..]).unwrap_or("");
+                    continue;
+                }
+                let tag_end = rest.find('>').map(|e| e + 1).unwrap_or(rest.len());
+                let tag = rest[1..tag_end].to_ascii_lowercase();
+                rest = &rest[tag_end..];
+                // block-level tags are word boundaries, inline markup like `H<sub>2</sub>O` isn't
+                let name: String = tag
+                    .trim_start_matches('/')
+                    .chars()
+                    .take_while(char::is_ascii_alphanumeric)
+                    .collect();
+                if crate::typo::BREAK_TAGS.contains(&name.as_str()) {
+                    push(&mut ret, ' ');
+                }
+                for skip in ["script", "style"] {
+                    if tag.starts_with(skip) {
+                        let close = format!("</{}", skip);
+                        rest = match rest.to_ascii_lowercase().find(&close) {
+                            Some(e) => &rest[e..],
+                            None => "",
+                        };
+                    }
+                }
+            }
+            '&' => {
+                let decoded = rest[1..]
+                    .find(';')
+                    .filter(|&e| e <= 10)
+                    .and_then(|e| Some((decode_entity(&rest[1..=e])?, e + 2)));
+                match decoded {
+                    Some((dc, elen)) => {
+                        push(&mut ret, dc);
+                        rest = &rest[elen..];
+                    }
+                    None => {
+                        push(&mut ret, '&');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            _ => {
+                push(&mut ret, c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    ret
 }
 
 pub fn is_not_hidden(entry: &DirEntry) -> bool {
@@ -134,28 +1035,63 @@ pub fn is_not_hidden(entry: &DirEntry) -> bool {
             .unwrap_or(false)
 }
 
-// source: https://users.rust-lang.org/t/convert-std-time-systemtime-to-chrono-datetime-datetime/7684/4
-pub fn system_time_to_date_time(t: SystemTime) -> DateTime<Utc> {
-    let (sec, nsec) = match t.duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(dur) => (dur.as_secs() as i64, dur.subsec_nanos()),
-        Err(e) => {
-            // unlikely but should be handled
-            let dur = e.duration();
-            let (sec, nsec) = (dur.as_secs() as i64, dur.subsec_nanos());
-            if nsec == 0 {
-                (-sec, 0)
-            } else {
-                (-sec - 1, 1_000_000_000 - nsec)
-            }
-        }
-    };
+/// returns `$SOURCE_DATE_EPOCH` if set, or the current time otherwise
+/// (see https://reproducible-builds.org/specs/source-date-epoch/)
+pub fn build_timestamp() -> DateTime<Utc> {
     use chrono::TimeZone;
-    Utc.timestamp_opt(sec, nsec).unwrap()
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|i| i.trim().parse::<i64>().ok())
+        .and_then(|i| Utc.timestamp_opt(i, 0).single())
+        .unwrap_or_else(Utc::now)
 }
 
+/// block-level tags whose presence prevents wrapping a section in `<p>`
+pub const DEFAULT_BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "audio",
+    "blockquote",
+    "canvas",
+    "code",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "fieldset",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "iframe",
+    "main",
+    "menu",
+    "nav",
+    "noscript",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "table",
+    "ul",
+    "video",
+];
+
 /// blog content mangler (inserts paragraph tags)
 pub struct Mangler {
     ahos: AhoCorasick,
+    line_breaks: bool,
+    typography: bool,
 }
 
 fn diiter<T>(a: T, b: T) -> impl Iterator<Item = T> {
@@ -163,32 +1099,44 @@ fn diiter<T>(a: T, b: T) -> impl Iterator<Item = T> {
     once(a).chain(once(b))
 }
 
+/// checks if the input contains any of the tags recognized by `ahos`,
+/// allowing attributes (`<div class="x">`) and self-closing tags (`<hr />`)
+fn contains_block_tag(ahos: &AhoCorasick, input: &str) -> bool {
+    ahos.find_iter(input).any(|m| {
+        matches!(
+            input[m.end()..].chars().next(),
+            Some('>' | '/' | ' ' | '\t' | '\r' | '\n')
+        )
+    })
+}
+
 struct SectionState<'i> {
     do_mangle: bool,
-    section: core::str::Lines<'i>,
+    section: core::iter::Peekable<core::str::Lines<'i>>,
 }
 
 pub struct MangleIter<'a, 'i> {
     ahos: &'a AhoCorasick,
+    line_breaks: bool,
     input: core::str::Split<'i, &'static str>,
     state: Option<SectionState<'i>>,
 }
 
 impl<'a, 'i> Iterator for MangleIter<'a, 'i> {
-    type Item = (bool, &'i str);
+    type Item = (bool, Cow<'i, str>);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             match self.state.take() {
                 None => {
                     let section = self.input.next()?;
-                    let do_mangle = !self.ahos.is_match(section);
+                    let do_mangle = !contains_block_tag(self.ahos, section);
                     self.state = Some(SectionState {
                         do_mangle,
-                        section: section.lines(),
+                        section: section.lines().peekable(),
                     });
                     if do_mangle {
-                        break Some((true, "<p>"));
+                        break Some((true, "<p>".into()));
                     }
                 }
                 Some(SectionState {
@@ -196,10 +1144,15 @@ impl<'a, 'i> Iterator for MangleIter<'a, 'i> {
                     mut section,
                 }) => {
                     if let Some(x) = section.next() {
+                        let x = if do_mangle && self.line_breaks && section.peek().is_some() {
+                            Cow::Owned(format!("{}<br />", x))
+                        } else {
+                            Cow::Borrowed(x)
+                        };
                         self.state = Some(SectionState { do_mangle, section });
                         break Some((do_mangle, x));
                     } else if do_mangle {
-                        break Some((true, "</p>"));
+                        break Some((true, "</p>".into()));
                     }
                 }
             }
@@ -208,24 +1161,452 @@ impl<'a, 'i> Iterator for MangleIter<'a, 'i> {
 }
 
 impl Mangler {
-    pub fn new(dont_mangle: &[&str]) -> Mangler {
+    pub fn new(dont_mangle: &[&str], line_breaks: bool) -> Mangler {
         let pats: Vec<_> = dont_mangle
             .iter()
-            .flat_map(|&i| diiter("<".to_string() + i + ">", "</".to_string() + i + ">"))
+            .flat_map(|&i| diiter("<".to_string() + i, "</".to_string() + i))
             .collect();
         Mangler {
+            // leftmost-longest, so that e.g. `<pre` isn't shadowed by `<p`
             ahos: AhoCorasickBuilder::new()
+                .ascii_case_insensitive(true)
+                .match_kind(MatchKind::LeftmostLongest)
                 .build(&pats)
                 .expect("unable to build mangle filter"),
+            line_breaks,
+            typography: false,
         }
     }
 
+    pub fn with_config(config: &MangleConfig) -> Mangler {
+        let mut tags: Vec<&str> = match &config.block_tags {
+            Some(x) => x.iter().map(String::as_str).collect(),
+            None => DEFAULT_BLOCK_TAGS.to_vec(),
+        };
+        tags.extend(config.extra_block_tags.iter().map(String::as_str));
+        let mut ret = Mangler::new(&tags, config.line_breaks);
+        ret.typography = config.typography;
+        ret
+    }
+
     /// You should only prepend each line with spaces if the associated $mangle boolean is 'true'.
     pub fn mangle_content<'a, 'i>(&'a self, input: &'i str) -> MangleIter<'a, 'i> {
         MangleIter {
             ahos: &self.ahos,
+            line_breaks: self.line_breaks,
             input: input.split("\n\n"),
             state: None,
         }
     }
+
+    /// applies the typography pass to mangled content, if enabled
+    pub fn typeset<'i>(&self, input: &'i str, lang: &str) -> Cow<'i, str> {
+        if self.typography {
+            Cow::Owned(crate::typo::typeset(input, lang))
+        } else {
+            Cow::Borrowed(input)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mangler() -> Mangler {
+        Mangler::new(&["div", "hr", "p", "pre", "table", "ul"], false)
+    }
+
+    /// returns for each blank-line separated section if it gets wrapped in `<p>`
+    fn decisions(input: &str) -> Vec<bool> {
+        let m = mangler();
+        input
+            .split("\n\n")
+            .map(|i| !contains_block_tag(&m.ahos, i))
+            .collect()
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Rust"), "rust");
+        assert_eq!(slugify("Über Straße"), "ueber-strasse");
+        assert_eq!(slugify("  Café #1? "), "cafe-1");
+        assert_eq!(slugify("C++"), "cplusplus");
+        assert_eq!(slugify("ﬁle"), "file");
+    }
+
+    #[test]
+    fn absolute_urls() {
+        let config: Config = serde_yaml::from_str(
+            "blog_name: x\nid: 'https://example.com/blog'\nauthor: x\nstylesheet: x\n",
+        )
+        .unwrap();
+        let fpath = Path::new("2021/0101hi.html");
+        assert_eq!(
+            absolute_url(&config, fpath, "a.png"),
+            "https://example.com/blog/2021/a.png"
+        );
+        assert_eq!(
+            absolute_url(&config, fpath, "/img/a.png"),
+            "https://example.com/img/a.png"
+        );
+        assert_eq!(
+            absolute_url(&config, Path::new("x.html"), "a.png"),
+            "https://example.com/blog/a.png"
+        );
+        assert_eq!(
+            absolute_url(&config, fpath, "https://cdn.example.org/a.png"),
+            "https://cdn.example.org/a.png"
+        );
+        assert!(is_absolute_href("/a.html"));
+        assert!(is_absolute_href("https://example.org/"));
+        assert!(!is_absolute_href("2021/a.html"));
+    }
+
+    #[test]
+    fn output_paths() {
+        let mut config: Config = serde_yaml::from_str(
+            "blog_name: x\nid: 'https://example.com/blog'\nauthor: x\nstylesheet: x\n",
+        )
+        .unwrap();
+        let mut post: Post = serde_yaml::from_str("title: x\ntyp: text\n").unwrap();
+        let srcrel = Utf8Path::new("fld/2021/0102nesting.md");
+        let cdate = NaiveDate::from_ymd_opt(2021, 1, 2).unwrap();
+        let op = |config: &Config, post: &Post| output_rel_path(config, srcrel, post, Some(cdate));
+
+        assert_eq!(op(&config, &post).unwrap(), "fld/2021/0102nesting.html");
+        for (src, out) in [
+            ("2021/0101v1.2.md", "2021/0101v1.2.html"),
+            ("2021/0101v1.2", "2021/0101v1.html"),
+            ("2021/0101a", "2021/0101a.html"),
+        ] {
+            let x = output_rel_path(&config, Utf8Path::new(src), &post, Some(cdate));
+            assert_eq!(x.unwrap(), out);
+        }
+        post.slug = Some("nested".to_string());
+        assert_eq!(op(&config, &post).unwrap(), "fld/2021/nested.html");
+
+        config.permalink = Some("{year}/{month}/{day}/{slug}.html".to_string());
+        assert_eq!(op(&config, &post).unwrap(), "2021/01/02/nested.html");
+        config.permalink = Some("{dir}/{slug}.html".to_string());
+        assert_eq!(op(&config, &post).unwrap(), "fld/2021/nested.html");
+        // undated pages keep their source path
+        assert_eq!(
+            output_rel_path(&config, Utf8Path::new("about.md"), &post, None).unwrap(),
+            "nested.html"
+        );
+
+        post.slug = Some("../x".to_string());
+        assert!(op(&config, &post).is_err());
+        post.slug = None;
+        config.permalink = Some("../{slug}.html".to_string());
+        assert!(op(&config, &post).is_err());
+    }
+
+    #[test]
+    fn fallback_titles() {
+        let mut post: Post = serde_yaml::from_str("typ: note\n").unwrap();
+        assert_eq!(
+            post.fallback_title("<p>Short &amp; <b>sweet</b></p>"),
+            "Short &amp; sweet"
+        );
+        assert_eq!(
+            post.fallback_title(&"word ".repeat(20)),
+            format!("{}\u{2026}", ["word"; 12].join(" "))
+        );
+        post.typ = PostTyp::Quote;
+        post.source = "<i>Someone</i>".to_string();
+        assert_eq!(post.fallback_title(""), "Someone");
+        post.typ = PostTyp::Image;
+        post.alt = "a \"cat\"".to_string();
+        assert_eq!(post.fallback_title("text"), "a &quot;cat&quot;");
+    }
+
+    #[test]
+    fn domains() {
+        assert_eq!(
+            url_domain("https://www.example.org/a/b"),
+            Some("example.org")
+        );
+        assert_eq!(
+            url_domain("http://user@sub.example.org:8080?x"),
+            Some("sub.example.org")
+        );
+        assert_eq!(url_domain("/local/path.html"), None);
+        assert_eq!(url_domain("file:///x"), None);
+    }
+
+    #[test]
+    fn series_order() {
+        let part = |series: &str, cdate: u32| {
+            let post: Post =
+                serde_yaml::from_str(&format!("title: x\ntyp: text\nseries: {}\n", series))
+                    .unwrap();
+            let cdate = NaiveDate::from_ymd_opt(2021, 1, cdate).unwrap();
+            let r = post.series.as_ref().unwrap();
+            assert_eq!(r.name(), "S");
+            (
+                r.part(),
+                IndexEntry::with_post_and_etc(&post, cdate, &cdate.to_string()),
+            )
+        };
+        let mut series = Series {
+            name: "S".to_string(),
+            parts: vec![
+                part("S", 1),
+                part("{name: S, part: 2}", 3),
+                part("S", 2),
+                part("{name: S, part: 1}", 4),
+            ],
+        };
+        series.prepare();
+        let order: Vec<_> = series
+            .parts
+            .iter()
+            .map(|(_, i)| chrono::Datelike::day(&i.cdate))
+            .collect();
+        assert_eq!(order, [4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn index_order() {
+        let ent = |title: &str, day: u32, pinned: bool| {
+            let post: Post = serde_yaml::from_str(&format!(
+                "title: '{}'\ntyp: text\npinned: {}\n",
+                title, pinned
+            ))
+            .unwrap();
+            let cdate = NaiveDate::from_ymd_opt(2021, 1, day).unwrap();
+            IndexEntry::with_post_and_etc(&post, cdate, title)
+        };
+        let titles = |order| {
+            let mut idx = Index {
+                order,
+                ents: vec![
+                    ent("b", 1, false),
+                    ent("<i>C</i>", 3, false),
+                    ent("a", 2, false),
+                    ent("z", 1, true),
+                ],
+                ..Default::default()
+            };
+            idx.prepare();
+            idx.ents.into_iter().map(|i| i.href).collect::<Vec<_>>()
+        };
+        assert_eq!(titles(SortOrder::DateDesc), ["z", "<i>C</i>", "a", "b"]);
+        assert_eq!(titles(SortOrder::DateAsc), ["z", "b", "a", "<i>C</i>"]);
+        assert_eq!(titles(SortOrder::Title), ["z", "a", "b", "<i>C</i>"]);
+    }
+
+    #[test]
+    fn dir_inheritance() {
+        let config: Config =
+            serde_yaml::from_str("blog_name: x\nid: x\nauthor: x\nstylesheet: a.css\nx_nav: nav\n")
+                .unwrap();
+        let mut metas = BTreeMap::new();
+        let meta = |s: &str| serde_yaml::from_str::<DirMeta>(s).unwrap();
+        metas.insert(
+            Utf8PathBuf::from("a"),
+            meta("stylesheet: b.css\nx_head: h\n"),
+        );
+        metas.insert(Utf8PathBuf::from("a/b"), meta("title: B\nx_head: ''\n"));
+
+        let (c, _) = dir_config(&config, &metas, Utf8Path::new("a/b/c"));
+        assert_eq!(
+            (c.stylesheet.as_str(), c.x_head.as_str(), c.x_nav.as_str()),
+            ("b.css", "", "nav")
+        );
+        let (c, _) = dir_config(&config, &metas, Utf8Path::new("a"));
+        assert_eq!((c.stylesheet.as_str(), c.x_head.as_str()), ("b.css", "h"));
+        assert!(matches!(
+            dir_config(&config, &metas, Utf8Path::new("x")).0,
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn langs() {
+        let config: Config =
+            serde_yaml::from_str("blog_name: x\nid: x\nauthor: x\nstylesheet: a.css\n").unwrap();
+        assert_eq!(config.lang, "de");
+        let post = |s: &str| serde_yaml::from_str::<Post>(s).unwrap();
+        assert_eq!(post("title: x\ntyp: text\n").lang(&config), "de");
+        assert_eq!(post("title: x\ntyp: text\nlang: en\n").lang(&config), "en");
+        assert_eq!(lang_name("en"), "English");
+        assert_eq!(lang_name("xx"), "xx");
+    }
+
+    #[test]
+    fn toml_dates() {
+        let data = "+++\ntitle = \"x\"\ntyp = \"text\"\nupdated = 2021-01-05\n\n[[changes]]\ndate = 2021-01-04\nnote = \"n\"\n+++\n";
+        let (fm, _) = crate::fmatter::split(data).unwrap();
+        let post: Post = fm.parse().unwrap();
+        let d = |day| NaiveDate::from_ymd_opt(2021, 1, day);
+        assert_eq!(post.updated, d(5));
+        assert_eq!(Some(post.changes[0].date), d(4));
+
+        let post: Post =
+            serde_yaml::from_str("title: x\ntyp: text\nupdated: 2021-01-05\n").unwrap();
+        assert_eq!(post.updated, d(5));
+        let (fm, _) = crate::fmatter::split(
+            "+++\ntitle = \"x\"\ntyp = \"text\"\nupdated = 2021-01-05T10:00:00\n+++\n",
+        )
+        .unwrap();
+        assert!(fm.parse::<Post>().is_err());
+    }
+
+    #[test]
+    fn site_paths() {
+        let mut config: Config =
+            serde_yaml::from_str("blog_name: x\nid: x\nauthor: x\nstylesheet: x\n").unwrap();
+        let mut sp = |id: &str| {
+            config.id = id.to_string();
+            site_path(&config).to_string()
+        };
+        assert_eq!(sp("https://example.com/blog/"), "/blog");
+        assert_eq!(sp("https://example.com"), "");
+        assert_eq!(sp("/blog"), "/blog");
+        assert_eq!(sp("tag:example.com,2021:blog"), "");
+        assert_eq!(sp("urn:uuid:60a76c80-d399-11d9-b93c-0003939e0af6"), "");
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(normalize_alias("/old/x.html").unwrap(), "old/x.html");
+        assert_eq!(normalize_alias("old/").unwrap(), "old/index.html");
+        assert!(normalize_alias("../x.html").is_err());
+        assert!(normalize_alias("a/./x.html").is_ok());
+    }
+
+    #[test]
+    fn strip() {
+        assert_eq!(
+            strip_html("<p>Foo &amp; <b>bar</b>\n  baz</p><!-- x -->&#x41;&#66;"),
+            "Foo & bar baz AB"
+        );
+        assert_eq!(strip_html("a<script>var x = '<b>';</script>b"), "ab");
+        assert_eq!(
+            strip_html("H<sub>2</sub>O, <em>Un</em>believable<br />x<li>y</li>"),
+            "H2O, Unbelievable x y"
+        );
+        assert_eq!(strip_html("a & b &unknown;"), "a & b &unknown;");
+    }
+
+    #[test]
+    fn mangle_plain() {
+        assert_eq!(decisions("hello\nworld\n\nfoo"), [true, true]);
+        assert_eq!(decisions("<b>bold</b> text"), [true]);
+    }
+
+    #[test]
+    fn mangle_simple_blocks() {
+        assert_eq!(decisions("<div>\nfoo\n</div>"), [false]);
+        assert_eq!(
+            decisions("<ul>\n  <li>a</li>\n</ul>\n\ntext"),
+            [false, true]
+        );
+    }
+
+    #[test]
+    fn mangle_attributes() {
+        assert_eq!(decisions("<div class=\"note\">\nfoo"), [false]);
+        assert_eq!(decisions("<pre lang=\"x\">\nfn main() {}"), [false]);
+        assert_eq!(decisions("<table id=t>\n<tr><td>1</td></tr>"), [false]);
+        assert_eq!(decisions("<div\n  class=\"multi-line\">"), [false]);
+    }
+
+    #[test]
+    fn mangle_closing_only() {
+        assert_eq!(
+            decisions("<div class=x>\nfoo\n\nbar\n\n</div>"),
+            [false, true, false]
+        );
+        assert_eq!(decisions("bar</div >"), [false]);
+    }
+
+    #[test]
+    fn mangle_self_closing() {
+        assert_eq!(decisions("<hr />"), [false]);
+        assert_eq!(decisions("<hr/>"), [false]);
+        assert_eq!(decisions("<hr>"), [false]);
+    }
+
+    #[test]
+    fn mangle_case_insensitive() {
+        assert_eq!(decisions("<DIV>\nfoo\n</DIV>"), [false]);
+        assert_eq!(decisions("<Pre Class=\"x\">"), [false]);
+    }
+
+    #[test]
+    fn mangle_prefix_lookalikes() {
+        // tags which merely start like a block tag must not count
+        assert_eq!(decisions("<progress value=1>"), [true]);
+        assert_eq!(decisions("<param>"), [true]);
+        assert_eq!(decisions("<hrx>"), [true]);
+        assert_eq!(decisions("<ulist>"), [true]);
+        assert_eq!(decisions("<divider/>"), [true]);
+        assert_eq!(decisions("<p"), [true]);
+        // ... but the longer tag must still be found
+        assert_eq!(decisions("<pre>"), [false]);
+        assert_eq!(decisions("<p class=\"lead\">"), [false]);
+    }
+
+    #[test]
+    fn mangle_output() {
+        let m = mangler();
+        let out: Vec<_> = m
+            .mangle_content("a\nb\n\n<div class=\"x\">c</div>")
+            .collect();
+        assert_eq!(
+            out,
+            [
+                (true, "<p>".into()),
+                (true, "a".into()),
+                (true, "b".into()),
+                (true, "</p>".into()),
+                (false, "<div class=\"x\">c</div>".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mangle_line_breaks() {
+        let m = Mangler::with_config(&MangleConfig {
+            line_breaks: true,
+            ..Default::default()
+        });
+        let out: Vec<_> = m.mangle_content("a\nb\nc\n\n<pre>\nx\ny</pre>").collect();
+        assert_eq!(
+            out,
+            [
+                (true, "<p>".into()),
+                (true, "a<br />".into()),
+                (true, "b<br />".into()),
+                (true, "c".into()),
+                (true, "</p>".into()),
+                (false, "<pre>".into()),
+                (false, "x".into()),
+                (false, "y</pre>".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mangle_config_tags() {
+        let m = Mangler::with_config(&MangleConfig {
+            extra_block_tags: vec!["custom-box".to_string()],
+            ..Default::default()
+        });
+        assert!(contains_block_tag(&m.ahos, "<figure>"));
+        assert!(contains_block_tag(&m.ahos, "<code>\nfn x() {}\n</code>"));
+        assert!(contains_block_tag(&m.ahos, "<custom-box a=b>"));
+        assert!(!contains_block_tag(&m.ahos, "<tt>x</tt>"));
+
+        let m = Mangler::with_config(&MangleConfig {
+            block_tags: Some(vec!["tt".to_string()]),
+            ..Default::default()
+        });
+        assert!(!contains_block_tag(&m.ahos, "<div>"));
+        assert!(contains_block_tag(&m.ahos, "<tt>x</tt>"));
+    }
 }